@@ -0,0 +1,79 @@
+// Writes $OUT_DIR/built_info.rs with the build metadata reported by /benchmark-info and /version.
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+	let output = Command::new(program).args(args).output().ok()?;
+	if !output.status.success() {
+		return None;
+	}
+	Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Formats unix seconds as an RFC 3339 UTC timestamp (days to civil date, Howard Hinnant's algorithm).
+fn rfc3339(secs: u64) -> String {
+	let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+	format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// Version of a direct dependency as resolved in Cargo.lock. The root package lists a
+/// dependency as `name` when a single version is locked and as `name version` otherwise.
+fn locked_version(lock: &str, name: &str) -> Option<String> {
+	let packages: Vec<&str> = lock.split("[[package]]").skip(1).collect();
+	let field = |package: &str, key: &str| package.lines()
+		.find_map(|line| line.strip_prefix(key)?.strip_prefix(" = \"")?.strip_suffix('"'))
+		.map(str::to_string);
+	let root = packages.iter().find(|package| field(package, "name").as_deref() == Some(env!("CARGO_PKG_NAME")))?;
+	let dependency = root.lines()
+		.filter_map(|line| line.trim().strip_prefix('"')?.strip_suffix("\","))
+		.find(|dependency| dependency.split(' ').next() == Some(name))?;
+	match dependency.split_once(' ') {
+		Some((_, version)) => Some(version.to_string()),
+		None => packages.iter().find(|package| field(package, "name").as_deref() == Some(name)).and_then(|package| field(package, "version")),
+	}
+}
+
+fn main() {
+	let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+	let rustc_version = command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string());
+	let git_hash = command_output("git", &["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+	let build_time = rfc3339(SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0));
+
+	let mut features: Vec<String> = env::vars()
+		.filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|feature| feature.to_lowercase()))
+		.collect();
+	features.sort();
+
+	let lock = fs::read_to_string(Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.lock")).unwrap_or_default();
+	let dependencies: Vec<(&str, String)> = ["actix-web", "rustls"].into_iter()
+		.map(|name| (name, locked_version(&lock, name).unwrap_or_else(|| "unknown".to_string())))
+		.collect();
+
+	let out = Path::new(&env::var("OUT_DIR").unwrap()).join("built_info.rs");
+	fs::write(out, format!(
+		"pub const GIT_HASH: &str = {:?};\npub const BUILD_TIME: &str = {:?};\npub const RUSTC_VERSION: &str = {:?};\npub const FEATURES: &[&str] = &{:?};\npub const DEPENDENCIES: &[(&str, &str)] = &{:?};\n",
+		git_hash, build_time, rustc_version, features, dependencies,
+	)).unwrap();
+
+	// console-subscriber only panics at startup without it
+	if env::var_os("CARGO_FEATURE_CONSOLE").is_some() && env::var_os("CARGO_CFG_TOKIO_UNSTABLE").is_none() {
+		println!("cargo:warning=the console feature needs RUSTFLAGS=\"--cfg tokio_unstable\" for --tokio-console to work");
+	}
+
+	println!("cargo:rerun-if-changed=build.rs");
+	println!("cargo:rerun-if-changed=Cargo.lock");
+	println!("cargo:rerun-if-changed=.git/HEAD");
+	println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -0,0 +1,204 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServerHandle, ServiceRequest, ServiceResponse},
+	http::{header, StatusCode},
+	middleware::Next,
+	web, HttpRequest, HttpResponse,
+};
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::error::BenchError;
+use crate::sequence::Sequence;
+use crate::slow::Warmup;
+use crate::status::MultiStatus;
+use crate::writes::ConcurrentWrites;
+
+/// Bearer token the `/admin/` routes require, `--admin-token`.
+pub struct AdminToken(pub String);
+
+/// Rejects `/admin/` requests without `Authorization: Bearer <token>` when `--admin-token`
+/// is set.
+pub async fn require_token(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	if let Some(AdminToken(token)) = req.app_data::<web::Data<AdminToken>>().map(|token| token.get_ref()) {
+		if req.path().starts_with("/admin/") {
+			let presented = req.headers().get(header::AUTHORIZATION)
+				.and_then(|value| value.to_str().ok())
+				.and_then(|value| value.strip_prefix("Bearer "));
+			// compares every byte, so the time taken doesn't tell how much of the token matched
+			let authorized = presented.is_some_and(|presented| presented.len() == token.len()
+				&& presented.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0);
+			if !authorized {
+				return Ok(req.error_response(BenchError::new(StatusCode::UNAUTHORIZED, "admin routes need Authorization: Bearer <admin token>")));
+			}
+		}
+	}
+	Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// Routes that can be switched off at runtime with `POST /admin/endpoints`, by path pattern.
+/// Routes disabled with `--disable-route` aren't registered and can't come back.
+pub struct Endpoints {
+	enabled: BTreeMap<&'static str, AtomicBool>,
+}
+
+impl Endpoints {
+	pub fn new(paths: impl IntoIterator<Item = &'static str>) -> Self {
+		// the admin routes stay, or the switch could lock itself out
+		let enabled = paths.into_iter()
+			.filter(|path| !path.starts_with("/admin/"))
+			.map(|path| (path, AtomicBool::new(true)))
+			.collect();
+		Endpoints{enabled}
+	}
+
+	fn is_enabled(&self, pattern: &str) -> bool {
+		self.enabled.get(pattern).is_none_or(|enabled| enabled.load(Ordering::Relaxed))
+	}
+
+	fn set(&self, path: &str, enabled: bool) -> Result<(), BenchError> {
+		let flag = self.enabled.get(path)
+			.ok_or_else(|| BenchError::bad_request(format!("{} is not a route that can be toggled", path)))?;
+		flag.store(enabled, Ordering::Relaxed);
+		Ok(())
+	}
+
+	fn body(&self) -> serde_json::Value {
+		let (enabled, disabled): (Vec<_>, Vec<_>) = self.enabled.iter().partition(|(_, enabled)| enabled.load(Ordering::Relaxed));
+		json!({
+			"enabled": enabled.into_iter().map(|(path, _)| *path).collect::<Vec<_>>(),
+			"disabled": disabled.into_iter().map(|(path, _)| *path).collect::<Vec<_>>(),
+		})
+	}
+}
+
+/// Answers requests to routes switched off at runtime like unknown paths, with a 404.
+pub async fn check_enabled(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	if let Some(endpoints) = req.app_data::<web::Data<Endpoints>>() {
+		if req.match_pattern().is_some_and(|pattern| !endpoints.is_enabled(&pattern)) {
+			return Ok(req.error_response(BenchError::not_found("no route matches the requested path")));
+		}
+	}
+	Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+#[derive(Deserialize)]
+pub struct EndpointsUpdate {
+	#[serde(default)]
+	enable: Vec<String>,
+	#[serde(default)]
+	disable: Vec<String>,
+}
+
+fn require_configured_token(req: &HttpRequest, what: &str) -> Result<(), BenchError> {
+	match req.app_data::<web::Data<AdminToken>>() {
+		Some(_) => Ok(()),
+		None => Err(BenchError::new(StatusCode::FORBIDDEN, format!("{} needs an --admin-token", what))),
+	}
+}
+
+/// `GET /admin/endpoints`: the routes that can be toggled, split into enabled and disabled.
+pub async fn endpoints(req: HttpRequest, endpoints: web::Data<Endpoints>) -> Result<HttpResponse, BenchError> {
+	require_configured_token(&req, "toggling endpoints")?;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(endpoints.body().to_string()))
+}
+
+/// `POST /admin/endpoints`: enables the route patterns in `enable` and disables those in
+/// `disable`, e.g. `{"disable": ["/post", "/prg/{id}"]}`, then answers like the GET. Nothing
+/// changes when a path is unknown.
+pub async fn update_endpoints(req: HttpRequest, update: web::Json<EndpointsUpdate>, endpoints: web::Data<Endpoints>) -> Result<HttpResponse, BenchError> {
+	require_configured_token(&req, "toggling endpoints")?;
+	if let Some(unknown) = update.enable.iter().chain(&update.disable).find(|path| !endpoints.enabled.contains_key(path.as_str())) {
+		return Err(BenchError::bad_request(format!("{} is not a route that can be toggled", unknown)));
+	}
+	for path in &update.enable {
+		endpoints.set(path, true)?;
+	}
+	for path in &update.disable {
+		endpoints.set(path, false)?;
+	}
+	info!("endpoints updated by admin request, enabled: {:?}, disabled: {:?}", update.enable, update.disable);
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(endpoints.body().to_string()))
+}
+
+/// `POST /admin/reset`: rewinds the server side counters, the `/slow-start` warmup included,
+/// and empties the write lists so a new benchmark run starts from a known state.
+pub async fn reset(multi_status: web::Data<MultiStatus>, sequence: web::Data<Sequence>, writes: web::Data<ConcurrentWrites>, warmup: web::Data<Warmup>) -> HttpResponse {
+	multi_status.reset();
+	sequence.reset();
+	writes.reset();
+	warmup.reset();
+	info!("counters reset by admin request");
+	HttpResponse::NoContent().finish()
+}
+
+/// The http and https servers, whose accepting of new connections `POST /admin/pause` and
+/// `POST /admin/resume` switch off and on.
+#[derive(Default)]
+pub struct Listeners {
+	handles: Mutex<Vec<ServerHandle>>,
+	paused: AtomicBool,
+}
+
+impl Listeners {
+	/// Adds a server once it runs, its handle only exists from then on.
+	pub fn register(&self, handle: ServerHandle) {
+		self.handles.lock().unwrap().push(handle);
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused.load(Ordering::Relaxed)
+	}
+
+	/// Whether anything changed, pausing twice is fine.
+	async fn set_paused(&self, paused: bool) -> bool {
+		if self.paused.swap(paused, Ordering::Relaxed) == paused {
+			return false;
+		}
+		let handles = self.handles.lock().unwrap().clone();
+		for handle in handles {
+			if paused {
+				handle.pause().await;
+			} else {
+				handle.resume().await;
+			}
+		}
+		true
+	}
+}
+
+async fn set_paused(req: HttpRequest, listeners: web::Data<Listeners>, paused: bool) -> Result<HttpResponse, BenchError> {
+	require_configured_token(&req, "pausing the listeners")?;
+	if listeners.set_paused(paused).await {
+		info!("{} accepting new connections by admin request", if paused { "stopped" } else { "resumed" });
+	}
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"paused": paused}).to_string()))
+}
+
+/// `POST /admin/pause`: stops accepting new connections until `/admin/resume`. The sockets
+/// stay bound, so new connections wait in the listen backlog, or time out once it is full,
+/// while established ones keep being served. The TCP echo and HTTP/3 listeners keep going.
+pub async fn pause(req: HttpRequest, listeners: web::Data<Listeners>) -> Result<HttpResponse, BenchError> {
+	set_paused(req, listeners, true).await
+}
+
+/// `POST /admin/resume`: accepts new connections again, the backlog first.
+pub async fn resume(req: HttpRequest, listeners: web::Data<Listeners>) -> Result<HttpResponse, BenchError> {
+	set_paused(req, listeners, false).await
+}
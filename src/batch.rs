@@ -0,0 +1,186 @@
+use actix_http::{BoxedPayloadStream, HttpMessage, Method, Request, Version};
+use actix_service::{IntoServiceFactory, Service, ServiceFactory};
+use actix_web::{
+	body::{self, BoxBody, MessageBody},
+	dev::{AppConfig, Payload, ServiceResponse},
+	http::{header::{self, HeaderName, HeaderValue}, StatusCode},
+	rt, web, HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::error::BenchError;
+use crate::trailing_slash;
+
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+/// Sub-responses are collected whole, a streaming endpoint would otherwise never end.
+const MAX_SUB_RESPONSE_SIZE: usize = 1024 * 1024;
+const SUB_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Most sub-requests one `/batch` request may carry, `--max-batch-size`.
+#[derive(Clone, Copy)]
+pub struct MaxBatchSize(pub usize);
+
+type BatchService = Rc<dyn Fn(Request) -> LocalBoxFuture<'static, Result<ServiceResponse<BoxBody>, actix_web::Error>>>;
+type ServiceBuilder = Box<dyn Fn(AppConfig) -> LocalBoxFuture<'static, Result<BatchService, String>> + Send>;
+
+/// In the extensions of every sub-request, `/batch` refuses requests carrying it whatever
+/// path routed them there.
+struct NestedBatch;
+
+thread_local! {
+	/// The app instance this worker runs sub-requests through, built on its first batch.
+	static SERVICE: RefCell<Option<BatchService>> = const { RefCell::new(None) };
+}
+
+/// Runs `/batch` sub-requests through the app itself, middlewares included, as if they had
+/// come in on their own.
+#[derive(Default)]
+pub struct Batch {
+	builder: Mutex<Option<ServiceBuilder>>,
+}
+
+impl Batch {
+	/// Hands over the factory the servers build the app with. The app holds this `Batch`
+	/// itself, so it can only be registered once the factory exists.
+	pub fn register<F, I, S, B>(&self, factory: F)
+	where
+		F: Fn() -> I + Send + 'static,
+		I: IntoServiceFactory<S, Request>,
+		S: ServiceFactory<Request, Config = AppConfig, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+		S::Service: 'static,
+		S::InitError: fmt::Debug,
+		B: MessageBody + 'static,
+	{
+		let builder: ServiceBuilder = Box::new(move |config| {
+			let service = factory().into_factory().new_service(config);
+			Box::pin(async move {
+				let service = Rc::new(service.await.map_err(|err| format!("{:?}", err))?);
+				let service: BatchService = Rc::new(move |req| {
+					let service = service.clone();
+					Box::pin(async move { Ok(service.call(req).await?.map_into_boxed_body()) })
+				});
+				Ok(service)
+			})
+		});
+		*self.builder.lock().unwrap() = Some(builder);
+	}
+
+	async fn service(&self, config: AppConfig) -> Result<BatchService, BenchError> {
+		if let Some(service) = SERVICE.with(|service| service.borrow().clone()) {
+			return Ok(service);
+		}
+		let build = self.builder.lock().unwrap().as_ref().map(|builder| builder(config))
+			.ok_or_else(|| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, "batch requests are not set up"))?;
+		let service = build.await
+			.map_err(|err| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("failed to start the batch app: {}", err)))?;
+		SERVICE.with(|slot| *slot.borrow_mut() = Some(service.clone()));
+		Ok(service)
+	}
+}
+
+#[derive(Deserialize)]
+pub struct SubRequest {
+	#[serde(default = "default_method")]
+	method: String,
+	path: String,
+	#[serde(default)]
+	headers: BTreeMap<String, String>,
+	/// sent as JSON, a string is sent as is
+	body: Option<Value>,
+}
+
+fn default_method() -> String {
+	"GET".to_string()
+}
+
+impl SubRequest {
+	fn request(self, outer: &HttpRequest) -> Result<Request, String> {
+		let method = Method::from_bytes(self.method.to_ascii_uppercase().as_bytes())
+			.map_err(|_| format!("invalid method {:?}", self.method))?;
+		if !self.path.starts_with('/') {
+			return Err(format!("path {:?} must start with /", self.path));
+		}
+		// as `--trailing-slash merge` would route it, `/batch/` and `//batch` included
+		let path = self.path.split_once('?').map_or(self.path.as_str(), |(path, _)| path);
+		if trailing_slash::canonical(path) == "/batch" {
+			return Err("batches can't be nested".to_string());
+		}
+
+		let (json, bytes) = match self.body {
+			None => (false, web::Bytes::new()),
+			Some(Value::String(text)) => (false, web::Bytes::from(text)),
+			Some(value) => (true, web::Bytes::from(value.to_string())),
+		};
+		let body = futures_util::stream::once(async move { Ok(bytes) });
+		let mut request = Request::with_payload(Payload::from(Box::pin(body) as BoxedPayloadStream));
+		request.extensions_mut().insert(NestedBatch);
+		let head = request.head_mut();
+		head.method = method;
+		head.uri = self.path.parse().map_err(|_| format!("invalid path {:?}", self.path))?;
+		head.version = Version::HTTP_11;
+		head.peer_addr = outer.peer_addr();
+		if let Some(host) = outer.headers().get(header::HOST) {
+			head.headers.insert(header::HOST, host.clone());
+		}
+		if json {
+			head.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+		}
+		for (name, value) in self.headers {
+			let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid header name {:?}", name))?;
+			let value = HeaderValue::from_str(&value).map_err(|_| format!("invalid value for header {}", name))?;
+			head.headers.insert(name, value);
+		}
+		Ok(request)
+	}
+}
+
+/// The body of a sub-response as JSON if it is JSON, as a string otherwise.
+fn body_value(bytes: &[u8]) -> Value {
+	serde_json::from_slice(bytes).unwrap_or_else(|_| String::from_utf8_lossy(bytes).into())
+}
+
+async fn sub_response(service: BatchService, request: Request) -> Value {
+	let response = match rt::time::timeout(SUB_REQUEST_TIMEOUT, service(request)).await {
+		Ok(Ok(response)) => response.into_parts().1,
+		Ok(Err(err)) => HttpResponse::from_error(err),
+		Err(_) => return json!({"status": null, "error": format!("no response within {:?}", SUB_REQUEST_TIMEOUT)}),
+	};
+	let status = response.status().as_u16();
+	match rt::time::timeout(SUB_REQUEST_TIMEOUT, body::to_bytes_limited(response.into_body(), MAX_SUB_RESPONSE_SIZE)).await {
+		Ok(Ok(Ok(bytes))) => json!({"status": status, "body": body_value(&bytes)}),
+		Ok(Ok(Err(err))) => json!({"status": status, "error": format!("response body failed: {}", err)}),
+		Ok(Err(_)) => json!({"status": status, "error": format!("response body is over {} bytes", MAX_SUB_RESPONSE_SIZE)}),
+		Err(_) => json!({"status": status, "error": format!("response body did not end within {:?}", SUB_REQUEST_TIMEOUT)}),
+	}
+}
+
+/// `POST /batch`: runs the sub-requests of a `[{"method", "path", "headers", "body"}, ...]`
+/// array through the app concurrently and answers with their `[{"status", "body"}, ...]` in
+/// the same order. Sub-requests get the full middleware stack, but no connection.
+pub async fn batch(req: HttpRequest, calls: web::Json<Vec<SubRequest>>, batch: web::Data<Batch>) -> Result<HttpResponse, BenchError> {
+	if req.extensions().contains::<NestedBatch>() {
+		return Err(BenchError::bad_request("batches can't be nested"));
+	}
+	let MaxBatchSize(max) = req.app_data::<MaxBatchSize>().copied().unwrap_or(MaxBatchSize(DEFAULT_MAX_BATCH_SIZE));
+	if calls.len() > max {
+		return Err(BenchError::bad_request(format!("a batch holds at most {} requests, this one has {}", max, calls.len())));
+	}
+	let requests = calls.into_inner().into_iter()
+		.enumerate()
+		.map(|(index, call)| call.request(&req).map_err(|err| BenchError::bad_request(format!("request {}: {}", index, err))))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	let service = batch.service(req.app_config().clone()).await?;
+	let responses = futures_util::future::join_all(requests.into_iter().map(|request| sub_response(service.clone(), request))).await;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(Value::Array(responses).to_string()))
+}
@@ -0,0 +1,79 @@
+use actix_web::{
+	body::MessageBody,
+	dev::{ServiceRequest, ServiceResponse},
+	http::header::{self, HeaderValue},
+	middleware::Next,
+	web,
+};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// `Cache-Control` of every response, `--cache-control`, and per path overrides,
+/// `--cache-control-path <path>=<value>`.
+pub struct CacheControl {
+	default: Option<HeaderValue>,
+	overrides: HashMap<String, HeaderValue>,
+}
+
+fn is_token(value: &str) -> bool {
+	!value.is_empty() && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// Checks the value is a list of `directive` or `directive=token|"quoted"` (RFC 9111
+/// section 5.2), so a typo fails at startup rather than in a cache.
+fn parse_value(value: &str) -> Result<HeaderValue> {
+	let value = value.trim();
+	for directive in value.split(',').map(str::trim) {
+		let valid = match directive.split_once('=') {
+			Some((name, argument)) => is_token(name) && (is_token(argument)
+				|| argument.len() >= 2 && argument.starts_with('"') && argument.ends_with('"') && !argument[1..argument.len() - 1].contains('"')),
+			None => is_token(directive),
+		};
+		if !valid {
+			return Err(anyhow!("invalid Cache-Control directive {:?} in {:?}", directive, value));
+		}
+	}
+	Ok(HeaderValue::from_str(value)?)
+}
+
+impl CacheControl {
+	/// Parses the default value and the `<path>=<value>` override specs.
+	pub fn parse<'a>(default: Option<&str>, specs: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+		let default = default.map(parse_value).transpose()?;
+		let mut overrides = HashMap::new();
+		for spec in specs {
+			let (path, value) = spec.split_once('=')
+				.filter(|(path, _)| path.starts_with('/'))
+				.ok_or_else(|| anyhow!("cache control override {:?} is not <path>=<value>", spec))?;
+			overrides.insert(path.trim().to_string(), parse_value(value)?);
+		}
+		Ok(CacheControl{default, overrides})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.default.is_none() && self.overrides.is_empty()
+	}
+}
+
+/// Sets `Cache-Control` on responses. A path override replaces whatever the handler sent,
+/// the default only fills in where the handler sent none, so `/sse` keeps its `no-cache`.
+pub async fn set_cache_control(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let (value, replace) = match req.app_data::<web::Data<CacheControl>>() {
+		Some(cache_control) => match cache_control.overrides.get(req.path()) {
+			Some(value) => (Some(value.clone()), true),
+			None => (cache_control.default.clone(), false),
+		},
+		None => (None, false),
+	};
+
+	let mut res = next.call(req).await?;
+	if let Some(value) = value {
+		if replace || !res.headers().contains_key(header::CACHE_CONTROL) {
+			res.headers_mut().insert(header::CACHE_CONTROL, value);
+		}
+	}
+	Ok(res)
+}
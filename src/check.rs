@@ -0,0 +1,64 @@
+use anyhow::{anyhow, bail, Result};
+use clap::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::tls::read_pem;
+
+/// Certificates closer to their expiry than this still pass, with a warning in the detail.
+const EXPIRY_WARNING_DAYS: i64 = 30;
+
+pub fn command() -> Command<'static> {
+	Command::new("check")
+		.about("Resolves the configuration given by the options and environment, checks the TLS files and that the ports can be bound, then exits without serving")
+}
+
+/// Results of the `check` subcommand, printed as they come in.
+#[derive(Default)]
+pub struct Checks {
+	failed: usize,
+	total: usize,
+}
+
+impl Checks {
+	/// Runs one check, its `Ok` value is the detail printed next to PASS.
+	pub fn run(&mut self, name: &str, check: impl FnOnce() -> Result<String>) {
+		self.total += 1;
+		match check() {
+			Ok(detail) => println!("PASS {}: {}", name, detail),
+			Err(err) => {
+				self.failed += 1;
+				println!("FAIL {}: {:#}", name, err);
+			}
+		}
+	}
+
+	/// Fails, so the process exits with 1, if any check did.
+	pub fn finish(self) -> Result<()> {
+		if self.failed > 0 {
+			bail!("{} of {} checks failed", self.failed, self.total);
+		}
+		println!("all {} checks passed", self.total);
+		Ok(())
+	}
+}
+
+/// Whether the first certificate in `cert_file_name` is valid now, and for how long still.
+pub fn certificate_validity(cert_file_name: &str) -> Result<String> {
+	let pem = read_pem(cert_file_name, "certificate")?;
+	let (_, pem) = x509_parser::pem::parse_x509_pem(&pem)
+		.map_err(|err| anyhow!("certificate file {} is not a valid PEM file: {}", cert_file_name, err))?;
+	let cert = pem.parse_x509()
+		.map_err(|err| anyhow!("{} does not start with an X.509 certificate: {}", cert_file_name, err))?;
+
+	let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+	let validity = cert.validity();
+	if validity.not_before.timestamp() > now {
+		bail!("{} is not valid before {}", cert_file_name, validity.not_before);
+	}
+	let days_left = (validity.not_after.timestamp() - now).div_euclid(24 * 60 * 60);
+	if validity.not_after.timestamp() <= now {
+		bail!("{} expired on {}", cert_file_name, validity.not_after);
+	}
+	let warning = if days_left < EXPIRY_WARNING_DAYS { ", renew it soon" } else { "" };
+	Ok(format!("{} for {} expires on {}, in {} days{}", cert_file_name, cert.subject(), validity.not_after, days_left, warning))
+}
@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{Arg, ArgMatches, Command};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+const QUANTILES: [(&str, f64); 3] = [("p50", 0.5), ("p90", 0.9), ("p99", 0.99)];
+
+pub fn command() -> Command<'static> {
+	Command::new("compare")
+		.about("Compares two /metrics captures, e.g. curl -s localhost:3000/metrics > run1.prom, per route")
+		.arg(Arg::with_name("baseline")
+			.value_name("baseline")
+			.required(true)
+			.index(1)
+			.help("Capture of the earlier run"))
+		.arg(Arg::with_name("candidate")
+			.value_name("candidate")
+			.required(true)
+			.index(2)
+			.help("Capture of the run compared against it"))
+		.arg(Arg::with_name("json")
+			.long("json")
+			.help("Print the comparison as JSON rather than text"))
+}
+
+/// What one capture says about one route.
+#[derive(Default)]
+struct RouteStats {
+	statuses: BTreeMap<String, f64>,
+	/// cumulative `(le, count)` buckets of `http_request_duration_seconds`
+	buckets: Vec<(f64, f64)>,
+}
+
+impl RouteStats {
+	fn requests(&self) -> f64 {
+		self.statuses.values().sum()
+	}
+
+	/// Estimated like PromQL's `histogram_quantile`, interpolating inside the bucket the
+	/// quantile falls in.
+	fn quantile(&self, q: f64) -> Option<f64> {
+		let mut buckets = self.buckets.clone();
+		buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+		let total = buckets.last()?.1;
+		if total == 0.0 {
+			return None;
+		}
+		let rank = q * total;
+		let (mut lower, mut below) = (0.0, 0.0);
+		for (le, count) in buckets {
+			if count >= rank {
+				if le.is_infinite() {
+					return Some(lower);
+				}
+				return Some(lower + (le - lower) * (rank - below) / (count - below).max(f64::MIN_POSITIVE));
+			}
+			(lower, below) = (le, count);
+		}
+		Some(lower)
+	}
+}
+
+/// One `name{label="value",...} value` line of the Prometheus text format.
+struct Sample<'a> {
+	name: &'a str,
+	labels: BTreeMap<String, String>,
+	value: f64,
+}
+
+/// `None` for comments and blank lines.
+fn parse_sample(line: &str) -> Result<Option<Sample<'_>>> {
+	let line = line.trim();
+	if line.is_empty() || line.starts_with('#') {
+		return Ok(None);
+	}
+	let (series, value) = line.rsplit_once(' ').ok_or_else(|| anyhow!("no value in {:?}", line))?;
+	let value = match value {
+		"+Inf" => f64::INFINITY,
+		value => value.parse::<f64>().with_context(|| format!("invalid value in {:?}", line))?,
+	};
+	let Some((name, labels)) = series.split_once('{') else {
+		return Ok(Some(Sample{name: series, labels: BTreeMap::new(), value}));
+	};
+	let labels = labels.strip_suffix('}').ok_or_else(|| anyhow!("unterminated labels in {:?}", line))?;
+
+	let mut parsed = BTreeMap::new();
+	let mut rest = labels;
+	while !rest.is_empty() {
+		let (label, after) = rest.split_once("=\"").ok_or_else(|| anyhow!("invalid labels in {:?}", line))?;
+		let mut value = String::new();
+		let mut chars = after.char_indices();
+		let end = loop {
+			match chars.next() {
+				Some((_, '\\')) => match chars.next() {
+					Some((_, 'n')) => value.push('\n'),
+					Some((_, escaped)) => value.push(escaped),
+					None => return Err(anyhow!("invalid labels in {:?}", line)),
+				},
+				Some((index, '"')) => break index,
+				Some((_, c)) => value.push(c),
+				None => return Err(anyhow!("unterminated label value in {:?}", line)),
+			}
+		};
+		parsed.insert(label.trim_start_matches(',').trim().to_string(), value);
+		rest = after[end + 1..].trim_start_matches(',');
+	}
+	Ok(Some(Sample{name, labels: parsed, value}))
+}
+
+/// Reads the request counters and duration histograms of a Prometheus text capture.
+fn load(file: &str) -> Result<BTreeMap<String, RouteStats>> {
+	let text = std::fs::read_to_string(file).with_context(|| format!("failed to read {}", file))?;
+	let mut routes: BTreeMap<String, RouteStats> = BTreeMap::new();
+	for (number, line) in text.lines().enumerate() {
+		let Some(Sample{name, labels, value}) = parse_sample(line).with_context(|| format!("{} line {}", file, number + 1))? else {
+			continue;
+		};
+		let Some(route) = labels.get("route") else {
+			continue;
+		};
+		match name {
+			"http_requests_total" => {
+				let status = labels.get("status").cloned().unwrap_or_default();
+				*routes.entry(route.clone()).or_default().statuses.entry(status).or_default() += value;
+			}
+			"http_request_duration_seconds_bucket" => {
+				let le = match labels.get("le").map(String::as_str) {
+					Some("+Inf") => f64::INFINITY,
+					Some(le) => le.parse::<f64>().with_context(|| format!("{} line {}: invalid bucket bound", file, number + 1))?,
+					None => continue,
+				};
+				routes.entry(route.clone()).or_default().buckets.push((le, value));
+			}
+			_ => {}
+		}
+	}
+	if routes.is_empty() {
+		return Err(anyhow!("{} has no http_requests_total samples, is it a /metrics capture?", file));
+	}
+	Ok(routes)
+}
+
+fn change(baseline: Option<f64>, candidate: Option<f64>) -> Value {
+	let relative = match (baseline, candidate) {
+		(Some(baseline), Some(candidate)) if baseline != 0.0 => Some((candidate - baseline) / baseline * 100.0),
+		_ => None,
+	};
+	json!({"baseline": baseline, "candidate": candidate, "change_percent": relative})
+}
+
+fn compare_route(baseline: Option<&RouteStats>, candidate: Option<&RouteStats>) -> Value {
+	let requests = |stats: Option<&RouteStats>| Some(stats.map_or(0.0, RouteStats::requests));
+	let statuses: BTreeSet<&String> = baseline.into_iter().chain(candidate).flat_map(|stats| stats.statuses.keys()).collect();
+	let count = |stats: Option<&RouteStats>, status: &str| Some(stats.and_then(|stats| stats.statuses.get(status)).copied().unwrap_or(0.0));
+
+	let statuses: Map<String, Value> = statuses.into_iter()
+		.map(|status| (status.clone(), change(count(baseline, status), count(candidate, status))))
+		.collect();
+	let latency: Map<String, Value> = QUANTILES.iter()
+		.map(|(name, q)| {
+			let quantile = |stats: Option<&RouteStats>| stats.and_then(|stats| stats.quantile(*q)).map(|seconds| seconds * 1000.0);
+			(name.to_string(), change(quantile(baseline), quantile(candidate)))
+		})
+		.collect();
+	json!({
+		"requests": change(requests(baseline), requests(candidate)),
+		"statuses": statuses,
+		"latency_ms": latency,
+	})
+}
+
+fn format_value(value: &Value) -> String {
+	match value.as_f64() {
+		Some(number) if number.fract() == 0.0 => format!("{}", number),
+		Some(number) => format!("{:.3}", number),
+		None => "-".to_string(),
+	}
+}
+
+fn format_change(label: &str, change: &Value) -> String {
+	let relative = match change["change_percent"].as_f64() {
+		Some(percent) => format!(" ({:+.1}%)", percent),
+		None => String::new(),
+	};
+	format!("  {:<16}{:>14} -> {:<14}{}", label, format_value(&change["baseline"]), format_value(&change["candidate"]), relative)
+}
+
+/// The `compare` subcommand: request counts, status mix and latency percentiles of every
+/// route in two `/metrics` captures side by side.
+pub fn run(matches: &ArgMatches) -> Result<()> {
+	let baseline_file = matches.value_of("baseline").unwrap_or_default();
+	let candidate_file = matches.value_of("candidate").unwrap_or_default();
+	let baseline = load(baseline_file)?;
+	let candidate = load(candidate_file)?;
+
+	let routes: BTreeSet<&String> = baseline.keys().chain(candidate.keys()).collect();
+	let comparison: Map<String, Value> = routes.into_iter()
+		.map(|route| (route.clone(), compare_route(baseline.get(route), candidate.get(route))))
+		.collect();
+
+	if matches.is_present("json") {
+		let body = json!({"baseline": baseline_file, "candidate": candidate_file, "routes": comparison});
+		println!("{}", serde_json::to_string_pretty(&body)?);
+		return Ok(());
+	}
+
+	println!("{} -> {}", baseline_file, candidate_file);
+	for (route, comparison) in &comparison {
+		println!("\n{}", route);
+		println!("{}", format_change("requests", &comparison["requests"]));
+		for (status, change) in comparison["statuses"].as_object().into_iter().flatten() {
+			println!("{}", format_change(&format!("status {}", status), change));
+		}
+		for (quantile, change) in comparison["latency_ms"].as_object().into_iter().flatten() {
+			println!("{}", format_change(&format!("{} ms", quantile), change));
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,509 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{Extensions, ServiceRequest, ServiceResponse},
+	http::{header, ConnectionType, StatusCode, Version},
+	middleware::Next,
+	rt, web, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
+use anyhow::anyhow;
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use std::os::fd::RawFd;
+use uuid::Uuid;
+
+use crate::error::BenchError;
+use crate::metrics::Metrics;
+use crate::stats::{Stats, Tracked};
+use crate::tls::{ClientCert, ConnectInfo, Handshake};
+
+/// How often a waiting handler checks whether its client is still there.
+const PEER_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_HOLD_MS: u64 = 60_000;
+/// Seconds an overflowing client is asked to wait before retrying.
+const OVERFLOW_RETRY_AFTER: u64 = 1;
+
+thread_local! {
+	/// Open connections of this worker, actix's `max_connections` is per worker as well.
+	static WORKER_CONNECTIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// What happens to connections beyond `--max-connections`, `--overflow-strategy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OverflowStrategy {
+	/// actix stops accepting, clients queue in the backlog or time out connecting
+	#[default]
+	Refuse,
+	/// accept anyway and answer every request on the connection with 503 and Retry-After
+	AcceptThen503,
+}
+
+impl FromStr for OverflowStrategy {
+	type Err = anyhow::Error;
+
+	fn from_str(strategy: &str) -> anyhow::Result<Self> {
+		match strategy {
+			"refuse" => Ok(OverflowStrategy::Refuse),
+			"accept-then-503" => Ok(OverflowStrategy::AcceptThen503),
+			_ => Err(anyhow!("unknown overflow strategy {}, expected refuse or accept-then-503", strategy)),
+		}
+	}
+}
+
+impl fmt::Display for OverflowStrategy {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			OverflowStrategy::Refuse => write!(f, "refuse"),
+			OverflowStrategy::AcceptThen503 => write!(f, "accept-then-503"),
+		}
+	}
+}
+
+/// Sees every connection the servers accept and attaches a [`Connection`] to it.
+pub struct Tracker {
+	metrics: Metrics,
+	stats: web::Data<Stats>,
+	/// log the lifecycle of every connection, `--trace-connections`
+	trace: bool,
+	/// concurrent requests per HTTP/2 connection, `--h2-max-streams`
+	h2_max_streams: Option<usize>,
+	/// connections per worker beyond which they only get 503s, under `accept-then-503`
+	overflow_limit: Option<usize>,
+	next_id: AtomicU64,
+}
+
+/// Lives in the connection's extensions, so it is dropped when the connection closes.
+pub struct Connection {
+	pub id: u64,
+	pub uuid: Uuid,
+	peer: Option<SocketAddr>,
+	opened: Instant,
+	requests: Cell<u64>,
+	trace: bool,
+	handshake: Option<Handshake>,
+	client_cert: Option<ClientCert>,
+	#[cfg(unix)]
+	fd: Option<RawFd>,
+	streams: Option<Arc<StreamLimit>>,
+	/// accepted beyond the `--max-connections` limit of its worker
+	overflow: bool,
+	_active: Tracked,
+}
+
+/// Admission of HTTP/2 requests on one connection, at most `max` at a time.
+struct StreamLimit {
+	max: usize,
+	permits: Semaphore,
+	waiting: AtomicUsize,
+}
+
+impl StreamLimit {
+	fn active(&self) -> usize {
+		self.max - self.permits.available_permits()
+	}
+}
+
+/// How a request got its stream slot, for `/h2-streams`.
+#[derive(Clone, Copy)]
+struct StreamAdmission {
+	waited: Duration,
+	active: usize,
+}
+
+impl Tracker {
+	pub fn new(metrics: Metrics, stats: web::Data<Stats>, trace: bool, h2_max_streams: Option<usize>, overflow_limit: Option<usize>) -> Self {
+		Tracker{metrics, stats, trace, h2_max_streams, overflow_limit, next_id: AtomicU64::new(1)}
+	}
+
+	/// Called for every new connection, after its TLS handshake if any.
+	pub fn on_connect(&self, info: ConnectInfo, ext: &mut Extensions) {
+		let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+		self.stats.connections.accepted.fetch_add(1, Ordering::Relaxed);
+		let worker_connections = WORKER_CONNECTIONS.with(|count| {
+			count.set(count.get() + 1);
+			count.get()
+		});
+		let overflow = self.overflow_limit.is_some_and(|limit| worker_connections > limit);
+		if overflow && self.trace {
+			info!("connection {} is over the limit of {} per worker, its requests get 503", id, worker_connections - 1);
+		}
+		if self.trace {
+			info!("connection {} accepted from {}", id, peer_name(info.peer));
+		}
+		if let Some(handshake) = &info.handshake {
+			self.metrics.observe_handshake(handshake.version, handshake.duration);
+			if self.trace {
+				info!("connection {} TLS handshake complete: {} in {:?}", id, handshake.version, handshake.duration);
+			}
+		}
+
+		ext.insert(Connection{
+			id,
+			uuid: Uuid::new_v4(),
+			peer: info.peer,
+			opened: Instant::now(),
+			requests: Cell::new(0),
+			trace: self.trace,
+			handshake: info.handshake,
+			client_cert: info.client_cert,
+			#[cfg(unix)]
+			fd: info.fd,
+			streams: self.h2_max_streams.map(|max| Arc::new(StreamLimit{max, permits: Semaphore::new(max), waiting: AtomicUsize::new(0)})),
+			overflow,
+			_active: Tracked::new(self.stats.clone(), |stats| &stats.connections.active),
+		});
+	}
+}
+
+impl Connection {
+	/// Whether the client has closed or reset the connection. actix only notices that once it
+	/// reads or writes again, which a handler waiting on an event doesn't do.
+	pub fn peer_closed(&self) -> bool {
+		#[cfg(unix)]
+		match (self.fd, self.peer) {
+			#[cfg(target_os = "linux")]
+			(Some(fd), Some(peer)) => !peer_connected(fd, peer),
+			_ => false,
+		}
+		#[cfg(not(unix))]
+		false
+	}
+
+	/// Sets SO_LINGER to zero, so closing the connection sends an RST instead of a FIN and
+	/// drops whatever is still unsent.
+	pub fn reset_on_close(&self) -> std::io::Result<()> {
+		#[cfg(unix)]
+		if let Some(fd) = self.fd {
+			// SAFETY: the fd belongs to this connection, which is open while its requests run
+			let socket = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) };
+			return socket2::SockRef::from(&socket).set_linger(Some(Duration::ZERO));
+		}
+		Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "the connection socket is not available"))
+	}
+}
+
+/// Reads the socket state from `TCP_INFO`, it stays `ESTABLISHED` until the peer sends its
+/// FIN or RST, unread data or not.
+#[cfg(target_os = "linux")]
+fn peer_connected(fd: RawFd, peer: SocketAddr) -> bool {
+	const TCP_ESTABLISHED: u8 = 1;
+	tcp_info(fd, peer).is_some_and(|info| info.tcpi_state == TCP_ESTABLISHED)
+}
+
+/// The kernel's `TCP_INFO` for the connection. An HTTP/2 request can outlive its connection,
+/// by then the fd is closed or even reused for another connection, hence the peer address
+/// check.
+#[cfg(target_os = "linux")]
+fn tcp_info(fd: RawFd, peer: SocketAddr) -> Option<libc::tcp_info> {
+	// SAFETY: the fd is only queried, a closed one makes the calls fail
+	let socket = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) };
+	if socket2::SockRef::from(&socket).peer_addr().ok().and_then(|addr| addr.as_socket()) != Some(peer) {
+		return None;
+	}
+	// SAFETY: tcp_info is plain data and getsockopt writes at most `len` bytes into it
+	let mut info: libc::tcp_info = unsafe { std::mem::zeroed() };
+	let mut len = std::mem::size_of::<libc::tcp_info>() as libc::socklen_t;
+	let result = unsafe {
+		libc::getsockopt(fd, libc::IPPROTO_TCP, libc::TCP_INFO, &mut info as *mut _ as *mut libc::c_void, &mut len)
+	};
+	(result == 0).then_some(info)
+}
+
+/// Resolves once the client of `req` went away, never if that can't be told.
+pub async fn disconnected(req: &HttpRequest) {
+	let mut ticker = actix_web::rt::time::interval(PEER_CHECK_INTERVAL);
+	loop {
+		ticker.tick().await;
+		if req.conn_data::<Connection>().is_some_and(Connection::peer_closed) {
+			return;
+		}
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		// the connection's service, and so this, is dropped on the worker that accepted it
+		WORKER_CONNECTIONS.with(|count| count.set(count.get().saturating_sub(1)));
+		if self.trace {
+			info!("connection {} from {} closed after {} requests, open for {:?}",
+				self.id, peer_name(self.peer), self.requests.get(), self.opened.elapsed());
+		}
+	}
+}
+
+fn peer_name(peer: Option<SocketAddr>) -> String {
+	peer.map_or_else(|| "unknown peer".to_string(), |peer| peer.to_string())
+}
+
+/// Counts the requests of every connection, telling first requests from keep-alive reuse.
+pub async fn track_requests(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	if let Some(connection) = req.conn_data::<Connection>() {
+		let count = connection.requests.get() + 1;
+		connection.requests.set(count);
+		if connection.trace {
+			if count == 1 {
+				info!("connection {} first request: {} {}", connection.id, req.method(), req.path());
+			} else {
+				info!("connection {} reused for request {}: {} {}", connection.id, count, req.method(), req.path());
+			}
+		}
+	}
+	next.call(req).await
+}
+
+/// `--overflow-strategy accept-then-503`: answers the requests of connections accepted beyond
+/// the limit with 503, `Retry-After` and `Connection: close`, an application level overload
+/// signal instead of a refused connection. HTTP/2 connections stay open, their client is
+/// expected to back off on the 503.
+pub async fn reject_overflow(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	if !req.conn_data::<Connection>().is_some_and(|connection| connection.overflow) {
+		return Ok(next.call(req).await?.map_into_boxed_body());
+	}
+	if let Some(stats) = req.app_data::<web::Data<Stats>>() {
+		stats.connections.overflow_rejected.fetch_add(1, Ordering::Relaxed);
+	}
+	let err = BenchError::new(StatusCode::SERVICE_UNAVAILABLE, "the server is at its connection limit, retry later");
+	let mut response = err.problem(Some(req.path().to_string())).error_response();
+	response.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from(OVERFLOW_RETRY_AFTER));
+	response.head_mut().set_connection_type(ConnectionType::Close);
+	Ok(req.into_response(response))
+}
+
+/// `--h2-max-streams`: holds HTTP/2 requests beyond the limit of their connection until an
+/// earlier one got its response. actix doesn't let the `SETTINGS_MAX_CONCURRENT_STREAMS` it
+/// advertises be changed, so the limit is enforced here and clients see extra streams wait
+/// instead of being refused. Streamed bodies may still be running when the slot is freed.
+pub async fn limit_streams(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let limit = match req.conn_data::<Connection>() {
+		Some(connection) if req.version() == Version::HTTP_2 => connection.streams.clone().map(|limit| (connection.id, limit)),
+		_ => None,
+	};
+	let Some((id, limit)) = limit else {
+		return next.call(req).await;
+	};
+
+	let start = Instant::now();
+	limit.waiting.fetch_add(1, Ordering::Relaxed);
+	let permit = limit.permits.acquire().await;
+	limit.waiting.fetch_sub(1, Ordering::Relaxed);
+	let active = limit.active();
+	debug!("connection {} stream admitted after {:?}: {} of {} active, {} waiting", id, start.elapsed(), active, limit.max, limit.waiting.load(Ordering::Relaxed));
+	req.extensions_mut().insert(StreamAdmission{waited: start.elapsed(), active});
+
+	let res = next.call(req).await;
+	drop(permit);
+	debug!("connection {} stream done: {} of {} active", id, limit.active(), limit.max);
+	res
+}
+
+#[derive(Deserialize)]
+pub struct H2StreamsQuery {
+	#[serde(default)]
+	hold_ms: u64,
+}
+
+/// `/h2-streams?hold_ms=<n>`: keeps the request's stream slot for `hold_ms`, then reports
+/// the connection's `--h2-max-streams` limit and how long the request waited for a slot.
+/// Opening more concurrent streams than the limit shows the extra ones waiting for the
+/// earlier ones to finish rather than failing.
+pub async fn h2_streams(req: HttpRequest, query: web::Query<H2StreamsQuery>) -> Result<HttpResponse, BenchError> {
+	if query.hold_ms > MAX_HOLD_MS {
+		return Err(BenchError::bad_request(format!("hold_ms must be at most {}", MAX_HOLD_MS)));
+	}
+	rt::time::sleep(Duration::from_millis(query.hold_ms)).await;
+
+	let limit = req.conn_data::<Connection>().and_then(|connection| connection.streams.clone())
+		.filter(|_| req.version() == Version::HTTP_2);
+	let admission = req.extensions().get::<StreamAdmission>().copied();
+	let body = json!({
+		"protocol": format!("{:?}", req.version()),
+		"max_streams": limit.as_ref().map(|limit| limit.max),
+		"active": limit.as_ref().map(|limit| limit.active()),
+		"waiting": limit.as_ref().map(|limit| limit.waiting.load(Ordering::Relaxed)),
+		"active_at_admission": admission.map(|admission| admission.active),
+		"waited_ms": admission.map(|admission| admission.waited.as_millis() as u64),
+		"hold_ms": query.hold_ms,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
+
+/// `/connection-id`: a UUID fixed for the lifetime of the connection, so clients can tell
+/// keep-alive reuse from new connections.
+pub async fn connection_id(req: HttpRequest) -> Result<HttpResponse, BenchError> {
+	let connection = req.conn_data::<Connection>()
+		.ok_or_else(|| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, "connection is not tracked"))?;
+	let body = json!({
+		"connection_id": connection.uuid.to_string(),
+		"requests": connection.requests.get(),
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
+
+/// `/connection-stats`: round trip time, congestion window and retransmissions of the
+/// request's TCP connection as the kernel sees them in `TCP_INFO`.
+#[cfg(target_os = "linux")]
+pub async fn connection_stats(req: HttpRequest) -> Result<HttpResponse, BenchError> {
+	let info = req.conn_data::<Connection>()
+		.and_then(|connection| tcp_info(connection.fd?, connection.peer?))
+		.ok_or_else(|| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, "TCP_INFO is not available for this connection"))?;
+	let body = json!({
+		"rtt_us": info.tcpi_rtt,
+		"rttvar_us": info.tcpi_rttvar,
+		"snd_cwnd": info.tcpi_snd_cwnd,
+		// over the connection's lifetime, tcpi_retransmits only counts the unrecovered ones
+		"retransmits": info.tcpi_total_retrans,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub async fn connection_stats() -> Result<HttpResponse, BenchError> {
+	Err(BenchError::new(StatusCode::NOT_IMPLEMENTED, "TCP_INFO is only read on Linux"))
+}
+
+/// `/proto`: the HTTP version the request came in with, `1.0`, `1.1`, `2` or `3`, and
+/// whether its connection is TLS. Not counted in `/metrics`.
+pub async fn proto(req: HttpRequest) -> HttpResponse {
+	let version = match req.version() {
+		Version::HTTP_09 => "0.9",
+		Version::HTTP_10 => "1.0",
+		Version::HTTP_11 => "1.1",
+		Version::HTTP_2 => "2",
+		Version::HTTP_3 => "3",
+		_ => "unknown",
+	};
+	let tls = req.version() == Version::HTTP_3 || req.conn_data::<Connection>().is_some_and(|connection| connection.handshake.is_some());
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"http_version": version, "tls": tls}).to_string())
+}
+
+/// `/tls-info`: the protocol version of the request's TLS connection and how long its
+/// handshake took, from accepting the socket (rustls) or the ClientHello (openssl) until it
+/// completed. `{"tls": false}` on plain connections.
+pub async fn tls_info(req: HttpRequest) -> HttpResponse {
+	let connection = req.conn_data::<Connection>();
+	let body = match connection.and_then(|connection| connection.handshake.map(|handshake| (connection, handshake))) {
+		Some((connection, handshake)) => json!({
+			"tls": true,
+			"version": handshake.version,
+			"handshake_us": handshake.duration.as_micros() as u64,
+			"handshake_ms": handshake.duration.as_secs_f64() * 1000.0,
+			"connection_id": connection.uuid.to_string(),
+		}),
+		None => json!({"tls": false}),
+	};
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string())
+}
+
+/// `/mutual-tls-info`: the certificate the client authenticated with under `--client-ca`,
+/// `{"mtls_enabled": false}` on connections without one.
+pub async fn mutual_tls_info(req: HttpRequest) -> HttpResponse {
+	let body = match req.conn_data::<Connection>().and_then(|connection| connection.client_cert.as_ref()) {
+		Some(cert) => json!({
+			"mtls_enabled": true,
+			"subject": cert.subject,
+			"issuer": cert.issuer,
+			"serial": cert.serial,
+			"not_after": cert.not_after,
+			"not_after_unix": cert.not_after_unix,
+		}),
+		None => json!({"mtls_enabled": false}),
+	};
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use actix_web::{middleware, App, HttpServer};
+	use serde_json::Value;
+
+	const MAX_STREAMS: usize = 2;
+	const HOLD_MS: u64 = 300;
+
+	/// Opens one stream on `client` for `/h2-streams` and returns when its response was
+	/// complete along with the body.
+	async fn h2_streams_request(client: h2::client::SendRequest<web::Bytes>, uri: String) -> (Instant, Value) {
+		let request = http::Request::get(uri).body(()).unwrap();
+		let (response, _) = client.ready().await.unwrap().send_request(request, true).unwrap();
+		let response = response.await.unwrap();
+		assert_eq!(response.status(), http::StatusCode::OK);
+		let mut body = response.into_body();
+		let mut data = Vec::new();
+		while let Some(chunk) = body.data().await {
+			let chunk = chunk.unwrap();
+			body.flow_control().release_capacity(chunk.len()).unwrap();
+			data.extend_from_slice(&chunk);
+		}
+		(Instant::now(), serde_json::from_slice(&data).unwrap())
+	}
+
+	#[actix_web::test]
+	async fn streams_over_the_limit_wait() {
+		let stats = web::Data::new(Stats::default());
+		let tracker = Arc::new(Tracker::new(Metrics::new(), stats, false, Some(MAX_STREAMS), None));
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		let server = HttpServer::new(|| App::new()
+			.wrap(middleware::from_fn(limit_streams))
+			.route("/h2-streams", web::get().to(h2_streams)))
+			.on_connect(move |conn, ext| tracker.on_connect(crate::tls::connect_info(conn), ext))
+			.workers(1)
+			.listen_auto_h2c(listener).unwrap()
+			.run();
+		let handle = server.handle();
+		rt::spawn(server);
+
+		let tcp = rt::net::TcpStream::connect(address).await.unwrap();
+		let (client, connection) = h2::client::handshake(tcp).await.unwrap();
+		let connection = rt::spawn(async move { connection.await.ok() });
+		let uri = format!("http://{}/h2-streams?hold_ms={}", address, HOLD_MS);
+		let streams = 2 * MAX_STREAMS;
+		let mut responses = futures_util::future::join_all((0..streams).map(|_| h2_streams_request(client.clone(), uri.clone()))).await;
+		connection.abort();
+		handle.stop(true).await;
+
+		responses.sort_by_key(|(finished, _)| *finished);
+		let (first, extra) = responses.split_at(MAX_STREAMS);
+		for (_, body) in first {
+			assert_eq!(body["protocol"], "HTTP/2.0");
+			assert_eq!(body["max_streams"], MAX_STREAMS);
+			assert!(body["waited_ms"].as_u64().unwrap() < HOLD_MS / 2, "admitted right away: {}", body);
+			assert!(body["active_at_admission"].as_u64().unwrap() <= MAX_STREAMS as u64);
+		}
+		// the extra streams were held until a slot freed up, then served like the others
+		for (_, body) in extra {
+			assert!(body["waited_ms"].as_u64().unwrap() >= HOLD_MS / 2, "waited for a slot: {}", body);
+			assert!(body["active_at_admission"].as_u64().unwrap() <= MAX_STREAMS as u64);
+		}
+	}
+}
@@ -0,0 +1,52 @@
+use actix_web::{
+	body::MessageBody,
+	dev::{ServiceRequest, ServiceResponse},
+	http::header::{self, HeaderValue},
+	middleware::Next,
+	web,
+};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// Response `Content-Type` overrides by request path, from `--content-type <path>=<mime>`.
+pub struct ContentTypes {
+	overrides: HashMap<String, HeaderValue>,
+}
+
+impl ContentTypes {
+	/// Parses `<path>=<mime>` specs, rejecting anything that isn't a valid MIME type.
+	pub fn parse<'a>(specs: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+		let mut overrides = HashMap::new();
+		for spec in specs {
+			let (path, content_type) = spec.split_once('=')
+				.filter(|(path, _)| path.starts_with('/'))
+				.ok_or_else(|| anyhow!("content type override {:?} is not <path>=<mime>", spec))?;
+			let content_type = content_type.trim().parse::<mime::Mime>()
+				.map_err(|err| anyhow!("invalid content type {:?} for {}: {}", content_type, path, err))?;
+			overrides.insert(path.trim().to_string(), HeaderValue::from_str(content_type.as_ref())?);
+		}
+		Ok(ContentTypes{overrides})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.overrides.is_empty()
+	}
+}
+
+/// Replaces the `Content-Type` of successful responses on overridden paths, the body is sent
+/// unchanged. Error responses keep their own type.
+pub async fn override_content_type(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let content_type = req.app_data::<web::Data<ContentTypes>>()
+		.and_then(|content_types| content_types.overrides.get(req.path()).cloned());
+
+	let mut res = next.call(req).await?;
+	if let Some(content_type) = content_type {
+		if res.status().is_success() {
+			res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+		}
+	}
+	Ok(res)
+}
@@ -0,0 +1,39 @@
+use actix_web::{cookie::{Cookie, SameSite}, http::header, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::BenchError;
+
+/// Cookie names are numbered with three digits.
+const MAX_COOKIES: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct SetCookiesQuery {
+	count: usize,
+}
+
+/// `/set-cookies?count=<n>`: sets `n` cookies `bench_cookie_000=value_000` onwards, one
+/// `Set-Cookie` header each with `Path=/`, `HttpOnly` and `SameSite=Lax`, and lists them in
+/// the body.
+pub async fn set_cookies(query: web::Query<SetCookiesQuery>) -> Result<HttpResponse, BenchError> {
+	if query.count > MAX_COOKIES {
+		return Err(BenchError::bad_request(format!("count must be at most {}", MAX_COOKIES)));
+	}
+
+	let mut response = HttpResponse::Ok();
+	let mut cookies = serde_json::Map::new();
+	for index in 0..query.count {
+		let (name, value) = (format!("bench_cookie_{:03}", index), format!("value_{:03}", index));
+		response.cookie(Cookie::build(name.clone(), value.clone()).path("/").http_only(true).same_site(SameSite::Lax).finish());
+		cookies.insert(name, value.into());
+	}
+
+	let body = json!({
+		"count": query.count,
+		"attributes": {"path": "/", "http_only": true, "same_site": "Lax"},
+		"cookies": cookies,
+	});
+	Ok(response
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
@@ -0,0 +1,100 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::{header::{self, HeaderValue}, Method, StatusCode},
+	middleware::Next,
+	web, HttpResponse, ResponseError,
+};
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+use crate::error::BenchError;
+
+/// How long browsers may cache a preflight answer, in seconds.
+const PREFLIGHT_MAX_AGE: u32 = 600;
+
+/// `--allow-origin`: the origins allowed to make cross-origin requests with credentials.
+pub struct AllowedOrigins {
+	origins: HashSet<String>,
+}
+
+impl AllowedOrigins {
+	/// Origins are `scheme://host[:port]` as browsers send them, `*` can't carry credentials.
+	pub fn parse<'a>(origins: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+		let mut allowed = HashSet::new();
+		for origin in origins {
+			let Some((scheme, host)) = origin.split_once("://") else {
+				bail!("invalid origin {:?}, expected scheme://host[:port]", origin);
+			};
+			if scheme.is_empty() || host.is_empty() || host.contains('/') {
+				bail!("invalid origin {:?}, expected scheme://host[:port] without a path", origin);
+			}
+			allowed.insert(origin.to_ascii_lowercase());
+		}
+		Ok(AllowedOrigins{origins: allowed})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.origins.is_empty()
+	}
+
+	fn allows(&self, origin: &str) -> bool {
+		self.origins.contains(&origin.to_ascii_lowercase())
+	}
+}
+
+/// The request's own credentials, browsers only send them in credentials mode.
+fn has_credentials(req: &ServiceRequest) -> bool {
+	req.headers().contains_key(header::COOKIE) || req.headers().contains_key(header::AUTHORIZATION)
+}
+
+fn allow(headers: &mut header::HeaderMap, origin: HeaderValue) {
+	headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+	headers.insert(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+	headers.append(header::VARY, HeaderValue::from_static("Origin"));
+}
+
+/// `--allow-origin`: answers preflights and lets the listed origins read responses with
+/// credentials. Credentialed requests and preflights from any other origin get a 403, other
+/// cross-origin requests are served without CORS headers, which browsers won't expose.
+pub async fn cors(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	let origin = match (req.app_data::<web::Data<AllowedOrigins>>(), req.headers().get(header::ORIGIN)) {
+		(Some(origins), Some(origin)) => Some((origin.clone(), origin.to_str().is_ok_and(|origin| origins.allows(origin)))),
+		_ => None,
+	};
+	let Some((origin, allowed)) = origin else {
+		return Ok(next.call(req).await?.map_into_boxed_body());
+	};
+	let preflight = req.method() == Method::OPTIONS && req.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+	if !allowed {
+		if !preflight && !has_credentials(&req) {
+			return Ok(next.call(req).await?.map_into_boxed_body());
+		}
+		let err = BenchError::new(StatusCode::FORBIDDEN, format!("origin {} is not allowed", String::from_utf8_lossy(origin.as_bytes())));
+		let response = err.problem(Some(req.path().to_string())).error_response();
+		return Ok(req.into_response(response));
+	}
+
+	if preflight {
+		let mut response = HttpResponse::NoContent();
+		for (request, allow) in [
+			(header::ACCESS_CONTROL_REQUEST_METHOD, header::ACCESS_CONTROL_ALLOW_METHODS),
+			(header::ACCESS_CONTROL_REQUEST_HEADERS, header::ACCESS_CONTROL_ALLOW_HEADERS),
+		] {
+			if let Some(value) = req.headers().get(request) {
+				response.insert_header((allow, value.clone()));
+			}
+		}
+		let mut response = response.insert_header((header::ACCESS_CONTROL_MAX_AGE, PREFLIGHT_MAX_AGE)).finish();
+		allow(response.headers_mut(), origin);
+		return Ok(req.into_response(response));
+	}
+
+	let mut res = next.call(req).await?.map_into_boxed_body();
+	allow(res.headers_mut(), origin);
+	Ok(res)
+}
@@ -0,0 +1,92 @@
+use actix_web::{http::{header, StatusCode, Version}, rt, web, HttpRequest, HttpResponse};
+use futures_util::Stream;
+use log::warn;
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::connection::Connection;
+use crate::error::BenchError;
+
+const MAX_AFTER: u64 = 64 * 1024 * 1024;
+/// Largest body `/simulate-timeout` announces, only `bytes_before_timeout` of it is sent.
+const MAX_TOTAL_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+const DEFAULT_TOTAL_SIZE: u64 = 1024 * 1024;
+const CHUNK: u64 = 64 * 1024;
+/// Time for the last bytes to leave the socket, the reset discards anything still queued.
+const FLUSH_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Deserialize)]
+pub struct ResetQuery {
+	#[serde(default)]
+	after: u64,
+}
+
+/// `/reset-connection?after=<bytes>`, only routed under `--allow-disruption`: sends the
+/// response head and `after` bytes of a chunked body, then resets the TCP connection instead
+/// of finishing the body. HTTP/1.x only, on HTTP/2 the reset would take every other stream of
+/// the connection with it.
+pub async fn reset_connection(req: HttpRequest, query: web::Query<ResetQuery>) -> Result<HttpResponse, BenchError> {
+	let after = query.after;
+	if after > MAX_AFTER {
+		return Err(BenchError::bad_request(format!("after must be at most {} bytes", MAX_AFTER)));
+	}
+	if req.version() >= Version::HTTP_2 {
+		return Err(BenchError::bad_request("connection resets are only supported over HTTP/1.x"));
+	}
+	reset_on_close(&req, after)?;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+	.streaming(cut_off_body(after)))
+}
+
+/// Makes closing the request's connection reset it.
+fn reset_on_close(req: &HttpRequest, after: u64) -> Result<(), BenchError> {
+	let connection = req.conn_data::<Connection>()
+		.ok_or_else(|| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, "connection is not tracked"))?;
+	connection.reset_on_close()
+		.map_err(|err| BenchError::new(StatusCode::NOT_IMPLEMENTED, format!("could not set SO_LINGER: {}", err)))?;
+	warn!("connection {} from {} will be reset after {} bytes", connection.id,
+		req.peer_addr().map_or_else(|| "unknown peer".to_string(), |peer| peer.to_string()), after);
+	Ok(())
+}
+
+/// `after` bytes, then an error instead of the rest of the body.
+fn cut_off_body(after: u64) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
+	futures_util::stream::unfold(0, move |sent| async move {
+		if sent < after {
+			let len = (after - sent).min(CHUNK);
+			return Some((Ok(web::Bytes::from(vec![b'x'; len as usize])), sent + len));
+		}
+		// failing the body makes actix close the connection, which SO_LINGER turns into an RST
+		rt::time::sleep(FLUSH_DELAY).await;
+		Some((Err(actix_web::error::ErrorInternalServerError("connection reset on purpose")), u64::MAX))
+	})
+}
+
+#[derive(Deserialize)]
+pub struct SimulateTimeoutQuery {
+	bytes_before_timeout: u64,
+	total_size: Option<u64>,
+}
+
+/// `/simulate-timeout?bytes_before_timeout=<n>&total_size=<s>`, only routed under
+/// `--allow-disruption`: announces a `Content-Length` of `total_size` but sends only `n`
+/// bytes of it before resetting the connection, like a server dying mid-response. Over
+/// HTTP/2 just the request's stream is reset.
+pub async fn simulate_timeout(req: HttpRequest, query: web::Query<SimulateTimeoutQuery>) -> Result<HttpResponse, BenchError> {
+	let after = query.bytes_before_timeout;
+	let total_size = query.total_size.unwrap_or(DEFAULT_TOTAL_SIZE);
+	if total_size > MAX_TOTAL_SIZE {
+		return Err(BenchError::bad_request(format!("total_size must be at most {} bytes", MAX_TOTAL_SIZE)));
+	}
+	if after >= total_size || after > MAX_AFTER {
+		return Err(BenchError::bad_request(format!("bytes_before_timeout must be below total_size and at most {}", MAX_AFTER)));
+	}
+	if req.version() < Version::HTTP_2 {
+		reset_on_close(&req, after)?;
+	}
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+	.no_chunking(total_size)
+	.streaming(cut_off_body(after)))
+}
@@ -0,0 +1,117 @@
+use actix_web::{
+	http::{header::{self, ByteRangeSpec, ContentRangeSpec, Header, Range}, StatusCode},
+	web, HttpRequest, HttpResponse,
+};
+use futures_util::{stream, Stream, StreamExt};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::error::BenchError;
+
+const DEFAULT_SIZE: u64 = 1024 * 1024;
+const MAX_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+const DEFAULT_CHUNK: usize = 64 * 1024;
+const MAX_CHUNK: usize = 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+	size: Option<u64>,
+	chunk: Option<usize>,
+}
+
+/// The byte ranges asked for, sorted and with overlapping or adjacent ones merged. `None`
+/// serves the whole file, ranges outside it are dropped unless none is left.
+fn requested_ranges(req: &HttpRequest, size: u64) -> Result<Option<Vec<(u64, u64)>>, BenchError> {
+	let specs: Vec<ByteRangeSpec> = match Range::parse(req) {
+		Ok(Range::Bytes(specs)) if !specs.is_empty() => specs,
+		_ => return Ok(None),
+	};
+	let mut ranges: Vec<(u64, u64)> = specs.iter().filter_map(|spec| spec.to_satisfiable_range(size)).collect();
+	if ranges.is_empty() {
+		// `bytes */<size>` tells the client the file's length, RFC 9110 section 15.5.17
+		return Err(BenchError::new(StatusCode::RANGE_NOT_SATISFIABLE, format!("no range is inside the {} byte file", size))
+			.with_header(header::ContentRange(ContentRangeSpec::Bytes{range: None, instance_length: Some(size)})));
+	}
+	ranges.sort_unstable();
+	let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+	for (start, end) in ranges {
+		match merged.last_mut() {
+			Some((_, last_end)) if start <= *last_end + 1 => *last_end = (*last_end).max(end),
+			_ => merged.push((start, end)),
+		}
+	}
+	Ok(Some(merged))
+}
+
+/// Bytes `start..start + length` of the file in `chunk` sized pieces, each a slice of
+/// `pattern` starting at its offset modulo 256.
+fn file_chunks(pattern: web::Bytes, start: u64, length: u64, chunk: usize) -> impl Stream<Item = Result<web::Bytes, actix_web::Error>> {
+	stream::unfold(start, move |offset| {
+		let pattern = pattern.clone();
+		async move {
+			if offset >= start + length {
+				return None;
+			}
+			let len = (start + length - offset).min(chunk as u64) as usize;
+			let skew = (offset % 256) as usize;
+			Some((Ok(pattern.slice(skew..skew + len)), offset + len as u64))
+		}
+	})
+}
+
+/// `/download?size=<bytes>&chunk=<bytes>`: a deterministic file served as an attachment and
+/// streamed in `chunk` sized pieces, byte `n` of the file being `n % 256`. `Range: bytes=`
+/// requests are answered with 206, a single range directly and several as
+/// `multipart/byteranges`, so resumed and parallel downloads can be checked too.
+pub async fn download(req: HttpRequest, query: web::Query<DownloadQuery>) -> Result<HttpResponse, BenchError> {
+	let size = query.size.unwrap_or(DEFAULT_SIZE);
+	let chunk = query.chunk.unwrap_or(DEFAULT_CHUNK);
+	if size > MAX_SIZE {
+		return Err(BenchError::bad_request(format!("size must be at most {} bytes", MAX_SIZE)));
+	}
+	if chunk == 0 || chunk > MAX_CHUNK {
+		return Err(BenchError::bad_request(format!("chunk must be between 1 and {} bytes", MAX_CHUNK)));
+	}
+
+	let pattern = web::Bytes::from((0..chunk + 255).map(|i| i as u8).collect::<Vec<u8>>());
+	let (mut response, start, length) = match requested_ranges(&req, size)? {
+		Some(ranges) if ranges.len() > 1 => return Ok(multipart_ranges(&ranges, size, pattern, chunk)),
+		Some(ranges) => {
+			let (start, end) = ranges[0];
+			let mut response = HttpResponse::PartialContent();
+			response.insert_header(header::ContentRange(ContentRangeSpec::Bytes{range: Some((start, end)), instance_length: Some(size)}));
+			(response, start, end - start + 1)
+		}
+		None => (HttpResponse::Ok(), 0, size),
+	};
+	Ok(response
+	.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+	.insert_header(header::ContentDisposition::attachment(format!("download-{}.bin", size)))
+	.insert_header((header::ACCEPT_RANGES, "bytes"))
+	.no_chunking(length)
+	.streaming(file_chunks(pattern, start, length, chunk)))
+}
+
+/// A `multipart/byteranges` 206 holding every range as a part with its own `Content-Range`.
+fn multipart_ranges(ranges: &[(u64, u64)], size: u64, pattern: web::Bytes, chunk: usize) -> HttpResponse {
+	// the file is a run of consecutive bytes, it can't contain a boundary with hex digits
+	let boundary = format!("bench-server-{:016x}", rand::thread_rng().gen::<u64>());
+	let parts: Vec<(web::Bytes, u64, u64)> = ranges.iter()
+		.map(|&(start, end)| {
+			let head = format!("\r\n--{}\r\nContent-Type: application/octet-stream\r\nContent-Range: bytes {}-{}/{}\r\n\r\n", boundary, start, end, size);
+			(web::Bytes::from(head), start, end - start + 1)
+		})
+		.collect();
+	let tail = web::Bytes::from(format!("\r\n--{}--\r\n", boundary));
+	let length = parts.iter().map(|(head, _, length)| head.len() as u64 + length).sum::<u64>() + tail.len() as u64;
+
+	let body = stream::iter(parts)
+		.flat_map(move |(head, start, length)| stream::once(async move { Ok(head) }).chain(file_chunks(pattern.clone(), start, length, chunk)))
+		.chain(stream::once(async move { Ok(tail) }));
+	HttpResponse::PartialContent()
+	.insert_header((header::CONTENT_TYPE, format!("multipart/byteranges; boundary={}", boundary)))
+	.insert_header(header::ContentDisposition::attachment(format!("download-{}.bin", size)))
+	.insert_header((header::ACCEPT_RANGES, "bytes"))
+	.no_chunking(length)
+	.streaming(body)
+}
@@ -0,0 +1,81 @@
+use actix_web::{http::header::{self, HeaderName}, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::error::BenchError;
+
+/// Largest message `/frame-echo` takes, the body as a whole is bounded by `--max-body-size`.
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+/// Big-endian `u32` in front of every message.
+const FRAME_HEADER: usize = 4;
+
+/// `/echo-query`: the decoded query string as a JSON object like httpbin's `args`, a key
+/// given once maps to its value and a repeated key to the array of its values in order.
+pub async fn echo_query(query: web::Query<Vec<(String, String)>>) -> HttpResponse {
+	let mut args = Map::new();
+	for (key, value) in query.into_inner() {
+		match args.get_mut(&key) {
+			Some(Value::Array(values)) => values.push(value.into()),
+			Some(first) => *first = Value::Array(vec![first.take(), value.into()]),
+			None => {
+				args.insert(key, value.into());
+			}
+		}
+	}
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(Value::Object(args).to_string())
+}
+
+#[derive(Deserialize)]
+pub struct MirrorHeaderQuery {
+	name: String,
+}
+
+/// `/mirror-header?name=<header>`: sends every value of the request header `name` back as
+/// `X-Mirrored-<name>`, with `X-Mirrored-Present` telling whether it arrived at all. Checks
+/// a proxy forwards one header without echoing the whole request.
+pub async fn mirror_header(req: HttpRequest, query: web::Query<MirrorHeaderQuery>) -> Result<HttpResponse, BenchError> {
+	let invalid = |_| BenchError::bad_request(format!("{:?} is not a valid header name", query.name));
+	let name = HeaderName::from_bytes(query.name.as_bytes()).map_err(invalid)?;
+	let mirrored = HeaderName::from_bytes(format!("x-mirrored-{}", name).as_bytes()).map_err(invalid)?;
+
+	let mut response = HttpResponse::Ok();
+	let mut present = false;
+	for value in req.headers().get_all(&name) {
+		response.append_header((mirrored.clone(), value.clone()));
+		present = true;
+	}
+	Ok(response
+	.insert_header(("X-Mirrored-Present", present.to_string()))
+	.finish())
+}
+
+/// `POST /frame-echo`: the body is a sequence of messages, each a 4 byte big-endian length
+/// followed by that many bytes, and every message goes back in order with the same framing.
+/// A message over 1MiB or cut short is a 400 naming its offset, so binary parsers on the
+/// client side can be checked against a known good one.
+pub async fn frame_echo(body: web::Bytes) -> Result<HttpResponse, BenchError> {
+	let mut frames = 0;
+	let mut offset = 0;
+	while offset < body.len() {
+		let header = body.get(offset..offset + FRAME_HEADER)
+			.ok_or_else(|| BenchError::bad_request(format!("message at offset {} is cut short in its length prefix", offset)))?;
+		let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+		if length > MAX_FRAME_SIZE {
+			return Err(BenchError::bad_request(format!("message at offset {} is {} bytes, the limit is {}", offset, length, MAX_FRAME_SIZE)));
+		}
+		let end = offset + FRAME_HEADER + length;
+		if end > body.len() {
+			return Err(BenchError::bad_request(format!("message at offset {} declares {} bytes, {} are left", offset, length, body.len() - offset - FRAME_HEADER)));
+		}
+		frames += 1;
+		offset = end;
+	}
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+	.insert_header(("X-Frame-Count", frames.to_string()))
+	// every message checked out, echoing them in order is echoing the body
+	.body(body))
+}
@@ -0,0 +1,198 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::{header, StatusCode},
+	middleware::Next,
+	web, HttpResponse, ResponseError,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::status::StatusBodies;
+
+/// Request header whose value is echoed back in error responses.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Error returned by the benchmark handlers, rendered as [`ProblemDetails`].
+#[derive(Debug, Clone)]
+pub struct BenchError {
+	status: StatusCode,
+	code: String,
+	message: String,
+	request_id: Option<String>,
+	extensions: Map<String, Value>,
+	/// sent with the problem details, e.g. `Content-Range` on a 416; rarely set, so boxed to
+	/// keep the `Err` of every handler small
+	headers: Option<Box<header::HeaderMap>>,
+}
+
+/// RFC 7807 problem details, the body of every error response:
+/// `{"type": "about:blank", "title": ..., "status": ..., "detail": ..., "instance": ...}` as
+/// `application/problem+json`, so clients can parse every failure the same way regardless of
+/// which endpoint produced it. `code` is the title in `snake_case`; `request_id` and any
+/// error specific members sit next to them.
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+	#[serde(rename = "type")]
+	type_uri: &'static str,
+	title: String,
+	status: u16,
+	detail: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	instance: Option<String>,
+	code: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	request_id: Option<String>,
+	#[serde(flatten)]
+	extensions: Map<String, Value>,
+}
+
+impl BenchError {
+	pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+		BenchError {
+			status,
+			code: status_code_name(status),
+			message: message.into(),
+			request_id: None,
+			extensions: Map::new(),
+			headers: None,
+		}
+	}
+
+	pub fn bad_request(message: impl Into<String>) -> Self {
+		Self::new(StatusCode::BAD_REQUEST, message)
+	}
+
+	pub fn not_found(message: impl Into<String>) -> Self {
+		Self::new(StatusCode::NOT_FOUND, message)
+	}
+
+	pub fn with_request_id(mut self, request_id: Option<String>) -> Self {
+		self.request_id = request_id;
+		self
+	}
+
+	/// Adds a member to the problem details, e.g. where a body failed to parse.
+	pub fn with_extension(mut self, name: &str, value: impl Into<Value>) -> Self {
+		self.extensions.insert(name.to_string(), value.into());
+		self
+	}
+
+	/// Adds a header to the error response, whatever body it ends up with.
+	pub fn with_header(mut self, header: impl header::TryIntoHeaderPair) -> Self {
+		if let Ok((name, value)) = header.try_into_pair() {
+			self.headers.get_or_insert_with(Default::default).insert(name, value);
+		}
+		self
+	}
+
+	fn add_headers(&self, response: &mut HttpResponse) {
+		for (name, value) in self.headers.iter().flat_map(|headers| headers.iter()) {
+			response.headers_mut().insert(name.clone(), value.clone());
+		}
+	}
+
+	/// The problem details of this error, `instance` being the request path when known.
+	pub fn problem(&self, instance: Option<String>) -> ProblemDetails {
+		ProblemDetails {
+			type_uri: "about:blank",
+			title: self.status.canonical_reason().unwrap_or("Error").to_string(),
+			status: self.status.as_u16(),
+			detail: self.message.clone(),
+			instance,
+			code: self.code.clone(),
+			request_id: self.request_id.clone(),
+			extensions: self.extensions.clone(),
+		}
+	}
+}
+
+impl fmt::Display for BenchError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.code, self.message)
+	}
+}
+
+impl std::error::Error for BenchError {}
+
+impl ResponseError for BenchError {
+	fn status_code(&self) -> StatusCode {
+		self.status
+	}
+
+	fn error_response(&self) -> HttpResponse {
+		let mut response = self.problem(None).error_response();
+		self.add_headers(&mut response);
+		response
+	}
+}
+
+impl fmt::Display for ProblemDetails {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} {}: {}", self.status, self.title, self.detail)
+	}
+}
+
+impl ResponseError for ProblemDetails {
+	fn status_code(&self) -> StatusCode {
+		StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+	}
+
+	fn error_response(&self) -> HttpResponse {
+		HttpResponse::build(self.status_code())
+			.insert_header((header::CONTENT_TYPE, PROBLEM_JSON))
+			.body(serde_json::to_string(self).unwrap_or_default())
+	}
+}
+
+/// `bad_request` style code derived from the status' canonical reason.
+fn status_code_name(status: StatusCode) -> String {
+	status
+		.canonical_reason()
+		.unwrap_or("error")
+		.to_ascii_lowercase()
+		.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+}
+
+/// Middleware that renders every error raised while handling a request as [`ProblemDetails`]
+/// for the request path, attaching the client supplied request id when there is one, or as
+/// the `--status-body` configured for its status.
+pub async fn problem_details(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	let request_id = req
+		.headers()
+		.get(REQUEST_ID_HEADER)
+		.and_then(|value| value.to_str().ok())
+		.map(str::to_string);
+
+	let instance = req.path().to_string();
+	let bodies = req.app_data::<web::Data<StatusBodies>>().cloned();
+	let res = next.call(req).await?.map_into_boxed_body();
+	let error = match res.response().error() {
+		Some(err) => match err.as_error::<BenchError>() {
+			Some(err) => err.clone(),
+			None => BenchError::new(res.status(), err.to_string()),
+		},
+		None => return Ok(res),
+	};
+
+	// `--status-body` replaces the problem details of its status
+	if let Some(body) = bodies.as_ref().and_then(|bodies| bodies.get(error.status)) {
+		let mut response = body.response(error.status);
+		error.add_headers(&mut response);
+		return Ok(res.into_response(response));
+	}
+	let error = error.with_request_id(request_id);
+	let mut response = error.problem(Some(instance)).error_response();
+	error.add_headers(&mut response);
+	Ok(res.into_response(response))
+}
+
+/// Fallback for unmatched routes.
+pub async fn not_found() -> Result<HttpResponse, BenchError> {
+	Err(BenchError::not_found("no route matches the requested path"))
+}
@@ -0,0 +1,44 @@
+use actix_web::{http::{header, StatusCode}, rt, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::error::BenchError;
+
+const MAX_TARGETS: usize = 10_000;
+const MAX_DELAY_MS: u64 = 60_000;
+
+#[derive(Deserialize)]
+pub struct FanoutQuery {
+	targets: usize,
+	delay_ms: u64,
+}
+
+/// `/benchmark/fanout?targets=<n>&delay_ms=<d>`: spawns `n` tasks that each wait `d` ms like
+/// a downstream call, then joins them. `total_ms` close to `d` means the calls overlapped,
+/// anything above is scheduling overhead.
+pub async fn fanout(query: web::Query<FanoutQuery>) -> Result<HttpResponse, BenchError> {
+	let FanoutQuery{targets, delay_ms} = query.into_inner();
+	if targets == 0 || targets > MAX_TARGETS {
+		return Err(BenchError::bad_request(format!("targets must be between 1 and {}", MAX_TARGETS)));
+	}
+	if delay_ms > MAX_DELAY_MS {
+		return Err(BenchError::bad_request(format!("delay_ms must be at most {}", MAX_DELAY_MS)));
+	}
+
+	let start = Instant::now();
+	let calls = (0..targets).map(|_| rt::spawn(rt::time::sleep(Duration::from_millis(delay_ms))));
+	for result in futures_util::future::join_all(calls).await {
+		result.map_err(|err| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("downstream task failed: {}", err)))?;
+	}
+	let total = start.elapsed();
+
+	let body = json!({
+		"fanout": targets,
+		"max_delay_ms": delay_ms,
+		"total_ms": total.as_secs_f64() * 1000.0,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
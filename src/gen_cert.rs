@@ -0,0 +1,74 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Arg, ArgMatches, Command};
+use log::*;
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair, PKCS_ECDSA_P256_SHA256, PKCS_RSA_SHA256};
+use rsa::pkcs8::{EncodePrivateKey, LineEnding};
+use std::path::Path;
+use time::{Duration, OffsetDateTime};
+
+const DEFAULT_DAYS: i64 = 365;
+const DEFAULT_CN: &str = "localhost";
+const RSA_BITS: usize = 2048;
+
+pub fn command() -> Command<'static> {
+	Command::new("gen-cert")
+		.about("Writes a self-signed key.pem and cert.pem for testing to the current directory")
+		.arg(Arg::with_name("days")
+			.long("days")
+			.value_name("days")
+			.takes_value(true)
+			.help("Validity from now, default 365"))
+		.arg(Arg::with_name("cn")
+			.long("cn")
+			.value_name("hostname")
+			.takes_value(true)
+			.help("Common name and subject alternative name, default localhost"))
+		.arg(Arg::with_name("key_type")
+			.long("key-type")
+			.value_name("ecdsa|rsa")
+			.takes_value(true)
+			.help("ECDSA P-256 or RSA 2048 key, default ecdsa"))
+		.arg(Arg::with_name("force")
+			.long("force")
+			.help("Overwrite existing key.pem and cert.pem"))
+}
+
+fn key_pair(key_type: &str) -> Result<KeyPair> {
+	match key_type {
+		"ecdsa" => Ok(KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256)?),
+		// rcgen can sign with RSA keys but not generate them
+		"rsa" => {
+			let key = rsa::RsaPrivateKey::new(&mut rand::rngs::OsRng, RSA_BITS)?;
+			let pem = key.to_pkcs8_pem(LineEnding::LF).map_err(|err| anyhow!("failed to encode the RSA key: {}", err))?;
+			Ok(KeyPair::from_pkcs8_pem_and_sign_algo(&pem, &PKCS_RSA_SHA256)?)
+		}
+		_ => bail!("unknown key type {}, expected ecdsa or rsa", key_type),
+	}
+}
+
+/// The `gen-cert` subcommand, a replacement for the openssl one-liner at the top of main.rs.
+pub fn run(matches: &ArgMatches) -> Result<()> {
+	let days = matches.value_of("days").map_or(Ok(DEFAULT_DAYS), str::parse::<i64>)?;
+	let cn = matches.value_of("cn").unwrap_or(DEFAULT_CN);
+	let key_type = matches.value_of("key_type").unwrap_or("ecdsa");
+	let (key_file, cert_file) = ("key.pem", "cert.pem");
+	if !matches.is_present("force") {
+		if let Some(existing) = [key_file, cert_file].into_iter().find(|file| Path::new(file).exists()) {
+			bail!("{} already exists, pass --force to overwrite it", existing);
+		}
+	}
+
+	let key_pair = key_pair(key_type)?;
+	let mut params = CertificateParams::new(vec![cn.to_string()])?;
+	params.distinguished_name = DistinguishedName::new();
+	params.distinguished_name.push(DnType::CommonName, cn);
+	params.not_before = OffsetDateTime::now_utc();
+	params.not_after = params.not_before + Duration::days(days);
+	let cert = params.self_signed(&key_pair)?;
+
+	std::fs::write(key_file, key_pair.serialize_pem()).with_context(|| format!("failed to write {}", key_file))?;
+	std::fs::write(cert_file, cert.pem()).with_context(|| format!("failed to write {}", cert_file))?;
+	info!("wrote {} and {}: self-signed {} certificate for {}, valid for {} days", key_file, cert_file, key_type, cn, days);
+	println!("wrote {} and {} for {}", key_file, cert_file, cn);
+	Ok(())
+}
@@ -0,0 +1,128 @@
+use actix_web::{http::{header, StatusCode}, web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use md5::Md5;
+use serde::Deserialize;
+use serde_json::json;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::time::{Duration, Instant};
+
+use crate::error::BenchError;
+use crate::MaxBodySize;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Algo {
+	#[default]
+	Sha256,
+	Sha512,
+	Sha1,
+	Blake3,
+	Md5,
+}
+
+impl Algo {
+	fn name(self) -> &'static str {
+		match self {
+			Algo::Sha256 => "sha256",
+			Algo::Sha512 => "sha512",
+			Algo::Sha1 => "sha1",
+			Algo::Blake3 => "blake3",
+			Algo::Md5 => "md5",
+		}
+	}
+
+	fn hasher(self) -> Hasher {
+		match self {
+			Algo::Sha256 => Hasher::Sha256(Sha256::new()),
+			Algo::Sha512 => Hasher::Sha512(Sha512::new()),
+			Algo::Sha1 => Hasher::Sha1(Sha1::new()),
+			Algo::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+			Algo::Md5 => Hasher::Md5(Md5::new()),
+		}
+	}
+}
+
+/// Incremental state of the chosen digest, fed one body chunk at a time.
+enum Hasher {
+	Sha256(Sha256),
+	Sha512(Sha512),
+	Sha1(Sha1),
+	Blake3(Box<blake3::Hasher>),
+	Md5(Md5),
+}
+
+impl Hasher {
+	fn update(&mut self, data: &[u8]) {
+		match self {
+			Hasher::Sha256(hasher) => hasher.update(data),
+			Hasher::Sha512(hasher) => hasher.update(data),
+			Hasher::Sha1(hasher) => hasher.update(data),
+			Hasher::Blake3(hasher) => {
+				hasher.update(data);
+			}
+			Hasher::Md5(hasher) => hasher.update(data),
+		}
+	}
+
+	fn finalize(self) -> Vec<u8> {
+		match self {
+			Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+			Hasher::Sha512(hasher) => hasher.finalize().to_vec(),
+			Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+			Hasher::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+			Hasher::Md5(hasher) => hasher.finalize().to_vec(),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+pub struct HashQuery {
+	#[serde(default)]
+	algo: Algo,
+}
+
+fn too_large(limit: usize) -> BenchError {
+	BenchError::new(StatusCode::PAYLOAD_TOO_LARGE, format!("body exceeds {} bytes", limit))
+}
+
+/// `POST /hash?algo=sha256|sha512|sha1|blake3|md5`: hashes the request body as it arrives,
+/// so memory stays bounded by a chunk, up to `--max-body-size` bytes. The algo defaults to
+/// sha256 and `duration_us` covers the hashing only.
+pub async fn hash(req: HttpRequest, query: web::Query<HashQuery>, mut body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	let algo = query.algo;
+	let limit = req.app_data::<MaxBodySize>().map_or(usize::MAX, |limit| limit.0);
+	let declared = req.headers().get(header::CONTENT_LENGTH)
+		.and_then(|length| length.to_str().ok())
+		.and_then(|length| length.parse::<usize>().ok());
+	if declared.is_some_and(|length| length > limit) {
+		return Err(too_large(limit).into());
+	}
+
+	let mut hasher = algo.hasher();
+	let mut bytes = 0;
+	let mut elapsed = Duration::ZERO;
+	while let Some(chunk) = body.next().await {
+		let chunk = chunk?;
+		bytes += chunk.len();
+		if bytes > limit {
+			return Err(too_large(limit).into());
+		}
+		let start = Instant::now();
+		hasher.update(&chunk);
+		elapsed += start.elapsed();
+	}
+	let start = Instant::now();
+	let digest = hasher.finalize();
+	elapsed += start.elapsed();
+
+	let body = json!({
+		"algo": algo.name(),
+		"hex": hex::encode(digest),
+		"bytes": bytes,
+		"duration_us": elapsed.as_micros() as u64,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
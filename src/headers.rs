@@ -0,0 +1,180 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::{header::{self, HeaderMap}, StatusCode},
+	middleware::Next,
+	web, HttpRequest, HttpResponse,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::error::BenchError;
+
+/// Header names are numbered with three digits.
+const MAX_HEADERS: usize = 1000;
+/// Upper bound of names plus values, well above what most clients accept by default.
+const MAX_TOTAL_HEADER_BYTES: usize = 256 * 1024;
+
+#[derive(Deserialize)]
+pub struct LargeHeadersQuery {
+	headers: usize,
+	size: usize,
+}
+
+/// Largest request header section accepted, `--max-request-header-size`. actix's own HTTP/1
+/// parser stops at 96 headers and 128KiB regardless.
+#[derive(Clone, Copy)]
+pub struct RequestHeaderLimit(pub usize);
+
+/// Size of the headers as sent on an HTTP/1.1 wire, `name: value\r\n` each.
+fn header_bytes(headers: &HeaderMap) -> usize {
+	headers.iter().map(|(name, value)| name.as_str().len() + value.len() + 4).sum()
+}
+
+/// Rejects requests whose headers exceed the `--max-request-header-size` limit with a 431.
+pub async fn limit_request_headers(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	if let Some(RequestHeaderLimit(limit)) = req.app_data::<RequestHeaderLimit>().copied() {
+		let bytes = header_bytes(req.headers());
+		if bytes > limit {
+			let err = BenchError::new(StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, format!("request headers take {} bytes, the limit is {}", bytes, limit));
+			return Ok(req.error_response(err));
+		}
+	}
+	Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// `/large-headers-request`: how many request headers arrived and how many bytes they took,
+/// the request side counterpart of `/large-headers`.
+pub async fn large_headers_request(req: HttpRequest) -> HttpResponse {
+	let largest = req.headers().iter()
+		.max_by_key(|(name, value)| name.as_str().len() + value.len())
+		.map(|(name, value)| json!({"name": name.as_str(), "bytes": name.as_str().len() + value.len() + 4}));
+	let body = json!({
+		"headers": req.headers().len(),
+		"header_bytes": header_bytes(req.headers()),
+		"largest": largest,
+		"limit": req.app_data::<RequestHeaderLimit>().map(|limit| limit.0),
+	});
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string())
+}
+
+fn header_name(index: usize) -> String {
+	format!("X-Bench-Header-{:03}", index)
+}
+
+/// `/large-headers?headers=<n>&size=<bytes>`: an empty body under `n` synthetic headers
+/// named `X-Bench-Header-000` onwards, each with a `size` byte value.
+pub async fn large_headers(query: web::Query<LargeHeadersQuery>) -> Result<HttpResponse, BenchError> {
+	let LargeHeadersQuery{headers, size} = query.into_inner();
+	if headers > MAX_HEADERS {
+		return Err(BenchError::bad_request(format!("headers must be at most {}", MAX_HEADERS)));
+	}
+	let total = size.checked_add(header_name(0).len()).and_then(|per_header| per_header.checked_mul(headers));
+	match total {
+		Some(total) if total <= MAX_TOTAL_HEADER_BYTES => {}
+		_ => return Err(BenchError::bad_request(format!("total header bytes must not exceed {}", MAX_TOTAL_HEADER_BYTES))),
+	}
+
+	let value = "x".repeat(size);
+	let mut response = HttpResponse::Ok();
+	response.insert_header(header::ContentType(mime::TEXT_PLAIN));
+	for index in 0..headers {
+		response.insert_header((header_name(index), value.as_str()));
+	}
+	Ok(response.finish())
+}
+
+/// The trailer fields a request announced in its `Trailer` header. Their values never reach
+/// a handler, see `echo_trailers`, so the body-echo endpoints report only the names.
+pub fn declared_trailers(req: &HttpRequest) -> Vec<&str> {
+	req.headers().get_all(header::TRAILER)
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.map(str::trim)
+		.filter(|name| !name.is_empty())
+		.collect()
+}
+
+/// `POST /echo-trailers`: drains the request body and reports the trailer fields the client
+/// announced in its `Trailer` header. actix-http discards the trailer section of chunked
+/// HTTP/1.1 bodies and ignores HTTP/2 trailers, so their values are never available here and
+/// `trailers` stays empty; the body still goes through the chunked trailer parsing path.
+pub async fn echo_trailers(req: HttpRequest, mut body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	let mut bytes = 0;
+	while let Some(chunk) = body.next().await {
+		bytes += chunk?.len();
+	}
+
+	let body = json!({
+		"bytes": bytes,
+		"chunked": req.headers().get(header::TRANSFER_ENCODING).is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"chunked")),
+		"declared_trailers": declared_trailers(&req),
+		"trailers": {},
+		"trailers_supported": false,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use actix_web::{App, HttpServer};
+	use serde_json::Value;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	/// Sends `request` as is to a server running `echo_trailers` and returns the response.
+	async fn raw_request(request: &[u8]) -> Vec<u8> {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		let server = HttpServer::new(|| App::new().route("/echo-trailers", web::post().to(echo_trailers)))
+			.workers(1)
+			.listen(listener).unwrap()
+			.run();
+		let handle = server.handle();
+		actix_web::rt::spawn(server);
+
+		let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+		stream.write_all(request).await.unwrap();
+		let mut response = Vec::new();
+		stream.read_to_end(&mut response).await.unwrap();
+		handle.stop(true).await;
+		response
+	}
+
+	#[actix_web::test]
+	async fn chunked_body_with_trailers() {
+		let response = raw_request(concat!(
+			"POST /echo-trailers HTTP/1.1\r\n",
+			"Host: localhost\r\n",
+			"Transfer-Encoding: chunked\r\n",
+			"Trailer: X-Checksum, X-Count\r\n",
+			"Connection: close\r\n",
+			"\r\n",
+			"5\r\nhello\r\n",
+			"6\r\n world\r\n",
+			"0\r\n",
+			"X-Checksum: 5eb63bbbe01eeed093cb22bb8f5acdc3\r\n",
+			"X-Count: 2\r\n",
+			"\r\n",
+		).as_bytes()).await;
+
+		let response = String::from_utf8(response).unwrap();
+		let (head, body) = response.split_once("\r\n\r\n").unwrap();
+		assert!(head.starts_with("HTTP/1.1 200"), "unexpected response: {}", head);
+		let body: Value = serde_json::from_str(body).unwrap();
+		assert_eq!(body["bytes"], 11);
+		assert_eq!(body["chunked"], true);
+		assert_eq!(body["declared_trailers"], serde_json::json!(["X-Checksum", "X-Count"]));
+		// actix-http parses the trailer section and drops it
+		assert_eq!(body["trailers"], serde_json::json!({}));
+		assert_eq!(body["trailers_supported"], false);
+	}
+}
@@ -0,0 +1,176 @@
+// Experimental HTTP/3 listener, only built with the `http3` feature. Requests are handed to
+// the same actix app the TCP servers run, on the main thread.
+use actix_http::{
+	body::{BodySize, BoxBody, MessageBody},
+	header::{self, HeaderName, HeaderValue},
+	BoxedPayloadStream, Method, Payload, Request, Response, Version,
+};
+use actix_service::{IntoServiceFactory, Service, ServiceFactory};
+use actix_web::{dev::AppConfig, error::PayloadError, rt, web::{Buf, Bytes}};
+use anyhow::{anyhow, Context, Result};
+use h3::server::RequestStream;
+use log::{debug, info};
+use quinn::rustls::{self, pki_types::{CertificateDer, PrivateKeyDer}};
+use rustls_pemfile::Item;
+use std::{fmt, future::poll_fn, io, net::SocketAddr, pin::pin, rc::Rc, sync::Arc};
+
+use crate::tls::{app_config, read_pem};
+
+/// Headers tied to a TCP connection, HTTP/3 forbids them (RFC 9114 section 4.2).
+const CONNECTION_SPECIFIC: &[&str] = &["connection", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade"];
+
+fn server_config(cert_file_name: &str, key_file_name: &str) -> Result<quinn::ServerConfig> {
+	let certs: Vec<CertificateDer<'static>> = rustls_pemfile::read_all(&mut read_pem(cert_file_name, "certificate")?.as_slice())
+		.with_context(|| format!("certificate file {} is not a valid PEM file", cert_file_name))?
+		.into_iter()
+		.filter_map(|item| match item {
+			Item::X509Certificate(der) => Some(der.into()),
+			_ => None,
+		})
+		.collect();
+	if certs.is_empty() {
+		return Err(anyhow!("no certificates found in {}", cert_file_name));
+	}
+	let key: PrivateKeyDer<'static> = rustls_pemfile::read_all(&mut read_pem(key_file_name, "private key")?.as_slice())
+		.with_context(|| format!("private key file {} is not a valid PEM file", key_file_name))?
+		.into_iter()
+		.find_map(|item| match item {
+			Item::PKCS8Key(der) => Some(PrivateKeyDer::Pkcs8(der.into())),
+			Item::RSAKey(der) => Some(PrivateKeyDer::Pkcs1(der.into())),
+			Item::ECKey(der) => Some(PrivateKeyDer::Sec1(der.into())),
+			_ => None,
+		})
+		.ok_or_else(|| anyhow!("no private key found in {}, expected a PKCS#8, RSA or EC PEM key", key_file_name))?;
+
+	let mut tls = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+		.with_protocol_versions(&[&rustls::version::TLS13])?
+		.with_no_client_auth()
+		.with_single_cert(certs, key)
+		.with_context(|| format!("private key {} does not match certificate {}", key_file_name, cert_file_name))?;
+	tls.alpn_protocols = vec![b"h3".to_vec()];
+	let quic = quinn::crypto::rustls::QuicServerConfig::try_from(tls)?;
+	Ok(quinn::ServerConfig::with_crypto(Arc::new(quic)))
+}
+
+/// Binds the QUIC endpoint, so a bad address or certificate fails startup.
+pub fn bind(addr: SocketAddr, cert_file_name: &str, key_file_name: &str) -> Result<quinn::Endpoint> {
+	let endpoint = quinn::Endpoint::server(server_config(cert_file_name, key_file_name)?, addr)
+		.with_context(|| format!("failed to bind the HTTP/3 endpoint on {}", addr))?;
+	info!("http3 server listen on {}", endpoint.local_addr()?);
+	Ok(endpoint)
+}
+
+/// Serves HTTP/3 on `endpoint` with one instance of the app, until the endpoint closes.
+pub async fn serve<F, I, S, B>(endpoint: quinn::Endpoint, factory: F) -> Result<()>
+where
+	F: Fn() -> I,
+	I: IntoServiceFactory<S, Request>,
+	S: ServiceFactory<Request, Config = AppConfig> + 'static,
+	S::Error: Into<actix_web::Error>,
+	S::InitError: fmt::Debug,
+	S::Response: Into<Response<B>>,
+	B: MessageBody + 'static,
+{
+	let addr = endpoint.local_addr()?;
+	let service = factory().into_factory().new_service(app_config(addr)).await
+		.map_err(|err| anyhow!("failed to start the HTTP/3 app: {:?}", err))?;
+	let service = Rc::new(service);
+
+	while let Some(incoming) = endpoint.accept().await {
+		let service = service.clone();
+		rt::spawn(async move {
+			if let Err(err) = connection(service, incoming).await {
+				debug!("http3 connection failed: {:#}", err);
+			}
+		});
+	}
+	Ok(())
+}
+
+async fn connection<S, B>(service: Rc<S>, incoming: quinn::Incoming) -> Result<()>
+where
+	S: Service<Request> + 'static,
+	S::Error: Into<actix_web::Error>,
+	S::Response: Into<Response<B>>,
+	B: MessageBody + 'static,
+{
+	let connection = incoming.await?;
+	let peer = connection.remote_address();
+	let mut connection = h3::server::Connection::new(h3_quinn::Connection::new(connection)).await?;
+	while let Some(resolver) = connection.accept().await? {
+		let service = service.clone();
+		rt::spawn(async move {
+			let result = async {
+				let (req, stream) = resolver.resolve_request().await?;
+				request(&*service, peer, req, stream).await
+			}.await;
+			if let Err(err) = result {
+				debug!("http3 request from {} failed: {:#}", peer, err);
+			}
+		});
+	}
+	Ok(())
+}
+
+/// Runs one request through the app, streaming both bodies.
+async fn request<S, B>(
+	service: &S,
+	peer: SocketAddr,
+	req: http::Request<()>,
+	stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+) -> Result<()>
+where
+	S: Service<Request>,
+	S::Error: Into<actix_web::Error>,
+	S::Response: Into<Response<B>>,
+	B: MessageBody + 'static,
+{
+	let (mut send, recv) = stream.split();
+	let body = futures_util::stream::unfold(Some(recv), |recv| async move {
+		let mut recv = recv?;
+		match recv.recv_data().await {
+			Ok(Some(mut chunk)) => Some((Ok(chunk.copy_to_bytes(chunk.remaining())), Some(recv))),
+			Ok(None) => None,
+			Err(err) => Some((Err(PayloadError::Io(io::Error::other(err))), None)),
+		}
+	});
+
+	let mut request = Request::with_payload(Payload::from(Box::pin(body) as BoxedPayloadStream));
+	let head = request.head_mut();
+	head.method = Method::from_bytes(req.method().as_str().as_bytes())?;
+	head.uri = req.uri().to_string().parse()?;
+	head.version = Version::HTTP_3;
+	head.peer_addr = Some(peer);
+	for (name, value) in req.headers() {
+		head.headers.append(HeaderName::from_bytes(name.as_str().as_bytes())?, HeaderValue::from_bytes(value.as_bytes())?);
+	}
+	// HTTP/3 carries the host in the :authority pseudo header only
+	if let Some(authority) = req.uri().authority().filter(|_| !head.headers.contains_key(header::HOST)) {
+		head.headers.insert(header::HOST, HeaderValue::from_str(authority.as_str())?);
+	}
+
+	let response: Response<BoxBody> = match service.call(request).await {
+		Ok(response) => response.into().map_into_boxed_body(),
+		Err(err) => err.into().into(),
+	};
+
+	let mut builder = http::Response::builder().status(response.status().as_u16());
+	for (name, value) in response.headers() {
+		if !CONNECTION_SPECIFIC.contains(&name.as_str()) {
+			builder = builder.header(name.as_str(), value.as_bytes());
+		}
+	}
+	// actix's h1/h2 encoders add it, here it's up to us
+	if let BodySize::Sized(length) = response.body().size() {
+		builder = builder.header(header::CONTENT_LENGTH.as_str(), length);
+	}
+	send.send_response(builder.body(())?).await?;
+
+	let mut body = pin!(response.into_body());
+	while let Some(chunk) = poll_fn(|cx| body.as_mut().poll_next(cx)).await {
+		let chunk = chunk.map_err(|err| anyhow!("response body failed: {}", err))?;
+		send.send_data(chunk).await?;
+	}
+	send.finish().await?;
+	Ok(())
+}
@@ -0,0 +1,229 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::{header::{HeaderName, HeaderValue}, StatusCode},
+	middleware::Next,
+	web,
+};
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use rsa::{pkcs1::DecodeRsaPublicKey, signature::Verifier, RsaPublicKey};
+use sha2::{Sha256, Sha512};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::BenchError;
+
+const SIGNATURE_HEADER: &str = "signature";
+const SIGNATURE_INPUT_HEADER: &str = "signature-input";
+
+const ED25519: &str = "ed25519";
+const RSA_V1_5_SHA256: &str = "rsa-v1_5-sha256";
+const RSA_PSS_SHA512: &str = "rsa-pss-sha512";
+
+enum PublicKey {
+	Ed25519(ed25519_dalek::VerifyingKey),
+	Rsa(RsaPublicKey),
+}
+
+/// Verifies HTTP message signatures (RFC 9421) against the `--verify-http-signature` key.
+/// Covers the derived components without parameters and plain header fields.
+pub struct SignatureVerifier {
+	key: PublicKey,
+}
+
+/// What a successfully verified request was signed with.
+struct Verified {
+	algorithm: &'static str,
+	key_id: Option<String>,
+}
+
+/// One `label=(components);params` member of `Signature-Input`.
+struct SignatureInput<'a> {
+	label: &'a str,
+	components: Vec<&'a str>,
+	params: Vec<(&'a str, &'a str)>,
+	/// the member value as sent, it is the `@signature-params` line of the signature base
+	raw: &'a str,
+}
+
+impl SignatureVerifier {
+	/// Loads an ed25519 or RSA public key from a PEM file, SPKI (`PUBLIC KEY`) or PKCS#1
+	/// (`RSA PUBLIC KEY`).
+	pub fn load(file_name: &str) -> Result<Self> {
+		let pem = std::fs::read_to_string(file_name)
+			.with_context(|| format!("failed to read signature public key {}", file_name))?;
+		let key = if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_pem(&pem) {
+			PublicKey::Ed25519(key)
+		} else if let Ok(key) = RsaPublicKey::from_public_key_pem(&pem) {
+			PublicKey::Rsa(key)
+		} else if let Ok(key) = RsaPublicKey::from_pkcs1_pem(&pem) {
+			PublicKey::Rsa(key)
+		} else {
+			return Err(anyhow!("{} is not an ed25519 or RSA public key in PEM format", file_name));
+		};
+		Ok(SignatureVerifier{key})
+	}
+
+	fn verify_request(&self, req: &ServiceRequest) -> Result<Verified, String> {
+		let inputs = header_str(req, SIGNATURE_INPUT_HEADER)?.ok_or("missing Signature-Input header")?;
+		let signatures = header_str(req, SIGNATURE_HEADER)?.ok_or("missing Signature header")?;
+
+		let inputs = split_members(&inputs).into_iter().map(parse_input).collect::<Result<Vec<_>, _>>()?;
+		let (input, signature) = inputs.iter().find_map(|input| {
+			split_members(&signatures).into_iter().find_map(|member| {
+				let (label, value) = member.split_once('=')?;
+				(label.trim() == input.label).then(|| (input, value.trim()))
+			})
+		}).ok_or("no Signature matches a Signature-Input label")?;
+
+		let signature = signature.strip_prefix(':').and_then(|value| value.strip_suffix(':'))
+			.and_then(|value| base64::engine::general_purpose::STANDARD.decode(value).ok())
+			.ok_or("signature is not a base64 byte sequence")?;
+
+		if let Some(expires) = input.param("expires") {
+			let expires = expires.parse::<u64>().map_err(|_| "invalid expires parameter")?;
+			let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0);
+			if expires < now {
+				return Err("signature expired".to_string());
+			}
+		}
+
+		let base = signature_base(req, input)?;
+		let algorithm = self.verify(input.param("alg"), base.as_bytes(), &signature)?;
+		Ok(Verified{algorithm, key_id: input.param("keyid").map(str::to_string)})
+	}
+
+	fn verify(&self, alg: Option<&str>, message: &[u8], signature: &[u8]) -> Result<&'static str, String> {
+		let candidates: &[&'static str] = match &self.key {
+			PublicKey::Ed25519(_) => &[ED25519],
+			PublicKey::Rsa(_) => &[RSA_V1_5_SHA256, RSA_PSS_SHA512],
+		};
+		let candidates: Vec<&'static str> = match alg {
+			Some(alg) => candidates.iter().copied().filter(|candidate| *candidate == alg).collect(),
+			None => candidates.to_vec(),
+		};
+		if candidates.is_empty() {
+			return Err(format!("algorithm {} does not match the configured key", alg.unwrap_or_default()));
+		}
+
+		candidates.into_iter().find(|algorithm| match (&self.key, *algorithm) {
+			(PublicKey::Ed25519(key), _) => ed25519_dalek::Signature::from_slice(signature)
+				.is_ok_and(|signature| key.verify_strict(message, &signature).is_ok()),
+			(PublicKey::Rsa(key), RSA_V1_5_SHA256) => rsa::pkcs1v15::Signature::try_from(signature)
+				.is_ok_and(|signature| rsa::pkcs1v15::VerifyingKey::<Sha256>::new(key.clone()).verify(message, &signature).is_ok()),
+			(PublicKey::Rsa(key), _) => rsa::pss::Signature::try_from(signature)
+				.is_ok_and(|signature| rsa::pss::VerifyingKey::<Sha512>::new(key.clone()).verify(message, &signature).is_ok()),
+		}).ok_or_else(|| "signature verification failed".to_string())
+	}
+}
+
+impl SignatureInput<'_> {
+	fn param(&self, name: &str) -> Option<&str> {
+		self.params.iter().find(|(key, _)| *key == name).map(|(_, value)| *value)
+	}
+}
+
+fn header_str(req: &ServiceRequest, name: &str) -> Result<Option<String>, String> {
+	let mut values = req.headers().get_all(name).peekable();
+	if values.peek().is_none() {
+		return Ok(None);
+	}
+	let values = values.map(|value| value.to_str().map(str::trim))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| format!("{} header is not valid ASCII", name))?;
+	Ok(Some(values.join(", ")))
+}
+
+/// Splits a structured field dictionary on the commas outside of quotes and inner lists.
+fn split_members(value: &str) -> Vec<&str> {
+	let (mut members, mut start, mut depth, mut quoted) = (Vec::new(), 0, 0, false);
+	for (index, c) in value.char_indices() {
+		match c {
+			'"' => quoted = !quoted,
+			'(' if !quoted => depth += 1,
+			')' if !quoted => depth -= 1,
+			',' if !quoted && depth == 0 => {
+				members.push(value[start..index].trim());
+				start = index + 1;
+			}
+			_ => {}
+		}
+	}
+	members.push(value[start..].trim());
+	members
+}
+
+fn parse_input(member: &str) -> Result<SignatureInput<'_>, String> {
+	let invalid = || format!("invalid Signature-Input member: {}", member);
+	let (label, raw) = member.split_once('=').ok_or_else(invalid)?;
+	let raw = raw.trim();
+	let (components, params) = raw.strip_prefix('(').and_then(|rest| rest.split_once(')')).ok_or_else(invalid)?;
+
+	let components = components.split_whitespace().map(|component| {
+		component.strip_prefix('"').and_then(|component| component.strip_suffix('"'))
+			.ok_or_else(|| format!("unsupported covered component: {}", component))
+	}).collect::<Result<Vec<_>, _>>()?;
+
+	let params = params.split(';').filter(|param| !param.is_empty()).map(|param| {
+		let (key, value) = param.split_once('=').unwrap_or((param, ""));
+		(key.trim(), value.trim().trim_matches('"'))
+	}).collect();
+
+	Ok(SignatureInput{label: label.trim(), components, params, raw})
+}
+
+fn signature_base(req: &ServiceRequest, input: &SignatureInput) -> Result<String, String> {
+	let mut base = String::new();
+	for component in &input.components {
+		let value = component_value(req, component)?;
+		base.push_str(&format!("\"{}\": {}\n", component, value));
+	}
+	base.push_str(&format!("\"@signature-params\": {}", input.raw));
+	Ok(base)
+}
+
+fn component_value(req: &ServiceRequest, component: &str) -> Result<String, String> {
+	let uri = req.uri();
+	let query = uri.query().map(|query| format!("?{}", query)).unwrap_or_default();
+	let value = match component {
+		"@method" => req.method().to_string(),
+		"@authority" => req.connection_info().host().to_ascii_lowercase(),
+		"@scheme" => req.connection_info().scheme().to_string(),
+		"@target-uri" => {
+			let info = req.connection_info();
+			format!("{}://{}{}{}", info.scheme(), info.host().to_ascii_lowercase(), uri.path(), query)
+		}
+		"@request-target" => format!("{}{}", uri.path(), query),
+		"@path" => uri.path().to_string(),
+		"@query" => if query.is_empty() { "?".to_string() } else { query },
+		derived if derived.starts_with('@') => return Err(format!("unsupported covered component: {}", derived)),
+		name => header_str(req, name)?.ok_or_else(|| format!("covered header {} is missing", name))?,
+	};
+	Ok(value)
+}
+
+/// Rejects requests without a valid signature with 401 when a verifier is configured, and
+/// reports the algorithm and key id of accepted ones as `X-Sig-Algo` and `X-Sig-Key-Id`.
+pub async fn verify(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	let verified = match req.app_data::<web::Data<SignatureVerifier>>() {
+		Some(verifier) => match verifier.verify_request(&req) {
+			Ok(verified) => Some(verified),
+			Err(message) => return Ok(req.error_response(BenchError::new(StatusCode::UNAUTHORIZED, message))),
+		},
+		None => None,
+	};
+
+	let mut res = next.call(req).await?.map_into_boxed_body();
+	if let Some(verified) = verified {
+		let headers = res.headers_mut();
+		headers.insert(HeaderName::from_static("x-sig-algo"), HeaderValue::from_static(verified.algorithm));
+		if let Some(key_id) = verified.key_id.and_then(|key_id| HeaderValue::from_str(&key_id).ok()) {
+			headers.insert(HeaderName::from_static("x-sig-key-id"), key_id);
+		}
+	}
+	Ok(res)
+}
@@ -0,0 +1,140 @@
+use actix_web::{
+	body::{self, BodySize, BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::{header::{HeaderMap, HeaderName}, Method, StatusCode},
+	middleware::Next,
+	web, HttpResponse,
+};
+use dashmap::{mapref::entry::Entry, DashMap};
+use std::time::{Duration, Instant};
+
+use crate::error::BenchError;
+
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// Set on responses served from the cache.
+const REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+/// Larger bodies, and streamed ones, are passed through without being cached.
+const MAX_CACHED_BODY: u64 = 1024 * 1024;
+/// Once this many keys are stored, expired ones are swept and a new key that still finds no
+/// room gets a 507.
+const MAX_ENTRIES: usize = 100_000;
+
+#[derive(Clone)]
+struct CachedResponse {
+	status: StatusCode,
+	headers: HeaderMap,
+	body: web::Bytes,
+	stored: Instant,
+}
+
+enum Slot {
+	/// the first request with the key is still running
+	InFlight,
+	Done(CachedResponse),
+}
+
+/// Responses to POST and PATCH requests by `Idempotency-Key`, kept for
+/// `--idempotency-key-ttl`.
+pub struct Idempotency {
+	ttl: Duration,
+	responses: DashMap<String, Slot>,
+}
+
+impl Idempotency {
+	pub fn new(ttl: Duration) -> Self {
+		Idempotency{ttl, responses: DashMap::new()}
+	}
+
+	fn is_live(&self, slot: &Slot) -> bool {
+		match slot {
+			Slot::InFlight => true,
+			Slot::Done(cached) => cached.stored.elapsed() < self.ttl,
+		}
+	}
+}
+
+/// Takes the key back if the first request fails or is dropped before its response was
+/// stored, so the client can retry.
+struct Pending<'a> {
+	idempotency: &'a Idempotency,
+	key: String,
+	done: bool,
+}
+
+impl Drop for Pending<'_> {
+	fn drop(&mut self) {
+		if !self.done {
+			self.idempotency.responses.remove_if(&self.key, |_, slot| matches!(slot, Slot::InFlight));
+		}
+	}
+}
+
+fn replay(cached: &CachedResponse) -> HttpResponse {
+	let mut response = HttpResponse::build(cached.status);
+	for (name, value) in &cached.headers {
+		response.append_header((name.clone(), value.clone()));
+	}
+	response.insert_header((REPLAYED, "true"));
+	response.body(cached.body.clone())
+}
+
+/// Replays the stored response of a POST or PATCH whose `Idempotency-Key` was seen within
+/// the TTL, instead of running the handler again. A retry while the first request is still
+/// running gets a 409. 5xx responses aren't stored, those requests may be retried.
+pub async fn deduplicate(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	let idempotency = req.app_data::<web::Data<Idempotency>>().cloned();
+	let key = req.headers().get(IDEMPOTENCY_KEY).and_then(|value| value.to_str().ok());
+	let (idempotency, key) = match (idempotency, key) {
+		(Some(idempotency), Some(key)) if [Method::POST, Method::PATCH].contains(req.method()) => {
+			// the same key on another endpoint is another operation
+			(idempotency, format!("{} {} {}", req.method(), req.path(), key))
+		}
+		_ => return Ok(next.call(req).await?.map_into_boxed_body()),
+	};
+
+	// len() locks every shard, it can't run while an entry is held
+	let full = idempotency.responses.len() >= MAX_ENTRIES && {
+		idempotency.responses.retain(|_, slot| idempotency.is_live(slot));
+		idempotency.responses.len() >= MAX_ENTRIES
+	};
+	let seen = match idempotency.responses.entry(key.clone()) {
+		Entry::Occupied(entry) if idempotency.is_live(entry.get()) => match entry.get() {
+			Slot::Done(cached) => Some(Ok(replay(cached))),
+			Slot::InFlight => Some(Err(BenchError::new(StatusCode::CONFLICT, "a request with this Idempotency-Key is still in progress"))),
+		},
+		Entry::Occupied(mut entry) => {
+			entry.insert(Slot::InFlight);
+			None
+		}
+		Entry::Vacant(_) if full => Some(Err(BenchError::new(StatusCode::INSUFFICIENT_STORAGE, format!("{} idempotency keys are already stored", MAX_ENTRIES)))),
+		Entry::Vacant(entry) => {
+			entry.insert(Slot::InFlight);
+			None
+		}
+	};
+	match seen {
+		Some(Ok(response)) => return Ok(req.into_response(response)),
+		Some(Err(err)) => return Ok(req.error_response(err)),
+		None => {}
+	}
+	let mut pending = Pending{idempotency: &idempotency, key, done: false};
+
+	let res = next.call(req).await?.map_into_boxed_body();
+	let cacheable = !res.status().is_server_error()
+		&& matches!(res.response().body().size(), BodySize::None | BodySize::Sized(0..=MAX_CACHED_BODY));
+	if !cacheable {
+		return Ok(res);
+	}
+
+	let (req, response) = res.into_parts();
+	let (response, body) = response.into_parts();
+	let body = body::to_bytes(body).await.map_err(|err| actix_web::error::ErrorInternalServerError(err.to_string()))?;
+	let cached = CachedResponse{status: response.status(), headers: response.headers().clone(), body: body.clone(), stored: Instant::now()};
+	idempotency.responses.insert(pending.key.clone(), Slot::Done(cached));
+	pending.done = true;
+	Ok(ServiceResponse::new(req, response.set_body(BoxBody::new(body))))
+}
@@ -0,0 +1,50 @@
+use actix_web::{http::{header, StatusCode}, rt, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::connection;
+use crate::error::BenchError;
+use crate::stats::{Stats, Tracked};
+
+const DEFAULT_INTERVAL_MS: u64 = 1000;
+const MAX_INTERVAL_MS: u64 = 60_000;
+/// Streams open at once, beyond this new ones get a 503.
+const MAX_STREAMS: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct ContinueStreamQuery {
+	interval_ms: Option<u64>,
+}
+
+/// `/continue-stream?interval_ms=<n>`: a JSON line `{"seq":N,"ts":<epoch_ms>}` every
+/// `interval_ms`, for as long as the client stays. The stream ends, and is counted out of
+/// `/stats`, on the failed write or the disconnect check, whichever notices first.
+pub async fn continue_stream(req: HttpRequest, query: web::Query<ContinueStreamQuery>, stats: web::Data<Stats>) -> Result<HttpResponse, BenchError> {
+	let interval = query.interval_ms.unwrap_or(DEFAULT_INTERVAL_MS);
+	if interval == 0 || interval > MAX_INTERVAL_MS {
+		return Err(BenchError::bad_request(format!("interval_ms must be between 1 and {}", MAX_INTERVAL_MS)));
+	}
+
+	let active = Tracked::new(stats.clone(), |stats| &stats.continue_stream.active);
+	if stats.continue_stream.active.load(Ordering::Relaxed) > MAX_STREAMS {
+		stats.continue_stream.rejected.fetch_add(1, Ordering::Relaxed);
+		return Err(BenchError::new(StatusCode::SERVICE_UNAVAILABLE, format!("{} streams are already open", MAX_STREAMS)));
+	}
+
+	let ticker = rt::time::interval(Duration::from_millis(interval));
+	let lines = futures_util::stream::unfold((ticker, 0u64, req, active), |(mut ticker, seq, req, active)| async move {
+		tokio::select! {
+			_ = ticker.tick() => {}
+			_ = connection::disconnected(&req) => return None,
+		}
+		let ts = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_millis() as u64);
+		let line = format!("{}\n", json!({"seq": seq, "ts": ts}));
+		Some((Ok::<_, actix_web::Error>(web::Bytes::from(line)), (ticker, seq + 1, req, active)))
+	});
+
+	Ok(HttpResponse::Ok()
+	.insert_header((header::CONTENT_TYPE, "application/x-ndjson"))
+	.streaming(lines))
+}
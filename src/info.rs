@@ -0,0 +1,37 @@
+use actix_web::{http::header, HttpResponse};
+use serde_json::json;
+
+mod built_info {
+	include!(concat!(env!("OUT_DIR"), "/built_info.rs"));
+}
+
+/// `/benchmark-info`: identifies the exact build that served a benchmark run.
+pub async fn benchmark_info() -> HttpResponse {
+	let body = json!({
+		"version": env!("CARGO_PKG_VERSION"),
+		"git_hash": built_info::GIT_HASH,
+		"build_time": built_info::BUILD_TIME,
+		"features": built_info::FEATURES,
+		"rustc_version": built_info::RUSTC_VERSION,
+	});
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string())
+}
+
+/// `/version`: the server version and the versions of the main dependencies it was built
+/// with, as locked in Cargo.lock.
+pub async fn version() -> HttpResponse {
+	let mut body = json!({
+		"server": env!("CARGO_PKG_NAME"),
+		"version": env!("CARGO_PKG_VERSION"),
+		// "rustc 1.75.0 (82e1608df 2023-12-21)"
+		"rust": built_info::RUSTC_VERSION.split_whitespace().nth(1).unwrap_or(built_info::RUSTC_VERSION),
+	});
+	for (name, version) in built_info::DEPENDENCIES {
+		body[name.replace('-', "_")] = json!(version);
+	}
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string())
+}
@@ -0,0 +1,24 @@
+use rand::Rng;
+use serde::Deserialize;
+
+/// `?jitter` on `/get`, `/post`, `/put` and `/delete`: adds a `"jitter"` field with a fresh
+/// random value to the JSON object, so no two response bodies are the same.
+#[derive(Deserialize)]
+pub struct JitterQuery {
+	jitter: Option<String>,
+}
+
+impl JitterQuery {
+	/// The body to send, with the jitter field as the first member of the object when asked
+	/// for. Bodies that aren't a JSON object are left alone.
+	pub fn apply(&self, body: &str) -> String {
+		let object = body.trim_start();
+		if self.jitter.is_none() || !object.starts_with('{') {
+			return body.to_string();
+		}
+
+		let members = &object[1..];
+		let separator = if members.trim_start().starts_with('}') { "" } else { "," };
+		format!("{{\"jitter\":\"{:016x}\"{}{}", rand::thread_rng().gen::<u64>(), separator, members)
+	}
+}
@@ -0,0 +1,144 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{Payload, ServiceRequest, ServiceResponse},
+	error::JsonPayloadError,
+	http::{header, Method, StatusCode},
+	middleware::Next,
+	web, HttpMessage, HttpRequest, HttpResponse,
+};
+use json_patch::{Patch, PatchOperation};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::BenchError;
+
+#[derive(Deserialize)]
+pub struct JsonPatchQuery {
+	base: Option<String>,
+}
+
+fn op_name(operation: &PatchOperation) -> &'static str {
+	match operation {
+		PatchOperation::Add(_) => "add",
+		PatchOperation::Remove(_) => "remove",
+		PatchOperation::Replace(_) => "replace",
+		PatchOperation::Move(_) => "move",
+		PatchOperation::Copy(_) => "copy",
+		PatchOperation::Test(_) => "test",
+	}
+}
+
+/// `PATCH /json-patch?base=<json>`: applies the RFC 6902 patch in the body to `base`, an
+/// empty object by default, and returns the result. A failing operation leaves nothing
+/// applied and is reported with its index as a 422.
+pub async fn json_patch(query: web::Query<JsonPatchQuery>, body: web::Bytes) -> Result<HttpResponse, BenchError> {
+	let mut document: Value = match &query.base {
+		Some(base) => serde_json::from_str(base).map_err(|err| BenchError::bad_request(format!("base is not valid JSON: {}", err)))?,
+		None => Value::Object(Default::default()),
+	};
+	let patch: Patch = serde_json::from_slice(&body)
+		.map_err(|err| BenchError::bad_request(format!("body is not a JSON patch: {}", err)))?;
+
+	json_patch::patch(&mut document, &patch).map_err(|err| {
+		let op = patch.get(err.operation).map_or("unknown", op_name);
+		BenchError::new(StatusCode::UNPROCESSABLE_ENTITY, format!("operation {} ({} {}) failed: {}", err.operation, op, err.path, err.kind))
+	})?;
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(document.to_string()))
+}
+
+/// Byte offset of a parse error, serde_json reports a 1-based line and column.
+fn error_position(body: &[u8], err: &serde_json::Error) -> usize {
+	let line_start: usize = body.split(|&byte| byte == b'\n').take(err.line().saturating_sub(1)).map(|line| line.len() + 1).sum();
+	(line_start + err.column().saturating_sub(1)).min(body.len())
+}
+
+fn error_category(err: &serde_json::Error) -> &'static str {
+	match err.classify() {
+		serde_json::error::Category::Io => "io",
+		serde_json::error::Category::Syntax => "syntax",
+		serde_json::error::Category::Data => "data",
+		serde_json::error::Category::Eof => "eof",
+	}
+}
+
+/// Where and why `err` happened, the body can't be shown an offset for when it's gone.
+fn parse_error(err: &serde_json::Error) -> BenchError {
+	let detail = match err.classify() {
+		serde_json::error::Category::Data => format!("body does not have the expected JSON shape: {}", err),
+		_ => format!("body is not valid JSON: {}", err),
+	};
+	BenchError::bad_request(detail)
+	.with_extension("valid", false)
+	.with_extension("error", err.to_string())
+	.with_extension("category", error_category(err))
+	.with_extension("line", err.line())
+	.with_extension("column", err.column())
+}
+
+/// The 400 answered for a body that isn't JSON, by `/validate-json`, the body endpoints and
+/// under `--strict-json`.
+fn invalid_json(body: &[u8], err: serde_json::Error) -> BenchError {
+	parse_error(&err).with_extension("position", error_position(body, &err))
+}
+
+/// Whether the request declares a JSON body, `application/json` or any `+json` type.
+fn declares_json(req: &HttpRequest) -> bool {
+	match req.mime_type() {
+		Ok(Some(mime)) => mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON),
+		_ => false,
+	}
+}
+
+/// Checks a body that declares itself JSON actually is, so the endpoints that otherwise
+/// ignore their body still tell a client where its JSON went wrong. Empty bodies pass.
+pub fn check_declared_body(req: &HttpRequest, body: &[u8]) -> Result<(), BenchError> {
+	if body.is_empty() || !declares_json(req) {
+		return Ok(());
+	}
+	serde_json::from_slice::<serde::de::IgnoredAny>(body).map(|_| ()).map_err(|err| invalid_json(body, err))
+}
+
+/// `web::Json` extraction errors in the same shape as the other body errors, rather than
+/// actix's plain text.
+pub fn extractor_error(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+	match err {
+		JsonPayloadError::Deserialize(err) => parse_error(&err).into(),
+		JsonPayloadError::ContentType => BenchError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "body must be declared as application/json").into(),
+		err => err.into(),
+	}
+}
+
+/// `POST /validate-json`: parses the body as JSON and reports its size, or why and where it
+/// isn't JSON with a 400.
+pub async fn validate_json(body: web::Bytes) -> Result<HttpResponse, BenchError> {
+	serde_json::from_slice::<serde::de::IgnoredAny>(&body).map_err(|err| invalid_json(&body, err))?;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"valid": true, "bytes": body.len()}).to_string()))
+}
+
+/// `--strict-json`: rejects POST, PUT and PATCH requests whose body isn't JSON before they
+/// reach the handler, which then reads the buffered body as usual. Empty bodies pass.
+pub async fn strict_json(
+	mut req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	if ![Method::POST, Method::PUT, Method::PATCH].contains(req.method()) {
+		return Ok(next.call(req).await?.map_into_boxed_body());
+	}
+	// bounded by the PayloadConfig limit, --max-body-size
+	let body = match req.extract::<web::Bytes>().await {
+		Ok(body) => body,
+		Err(err) => return Ok(req.error_response(err)),
+	};
+	if !body.is_empty() {
+		if let Err(err) = serde_json::from_slice::<serde::de::IgnoredAny>(&body) {
+			return Ok(req.error_response(invalid_json(&body, err)));
+		}
+	}
+	req.set_payload(Payload::Stream{payload: Box::pin(futures_util::stream::once(async move { Ok(body) }))});
+	Ok(next.call(req).await?.map_into_boxed_body())
+}
@@ -0,0 +1,61 @@
+use actix_web::{http::header, web, HttpResponse};
+use serde_json::{json, Value};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+
+fn error(code: i64, message: &str, id: Value) -> Value {
+	json!({"jsonrpc": "2.0", "error": {"code": code, "message": message}, "id": id})
+}
+
+/// Answers one request of a call, `None` for a notification.
+fn call(request: &Value) -> Option<Value> {
+	let Some(object) = request.as_object() else {
+		return Some(error(INVALID_REQUEST, "Invalid Request", Value::Null));
+	};
+	// an id of the wrong type can't be echoed back, the spec answers those with a null id
+	let id = match object.get("id") {
+		None => None,
+		Some(id @ (Value::String(_) | Value::Number(_) | Value::Null)) => Some(id.clone()),
+		Some(_) => return Some(error(INVALID_REQUEST, "Invalid Request: id must be a string, number or null", Value::Null)),
+	};
+	let invalid = if object.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+		Some("Invalid Request: jsonrpc must be \"2.0\"")
+	} else if !object.get("method").is_some_and(Value::is_string) {
+		Some("Invalid Request: method must be a string")
+	} else if object.get("params").is_some_and(|params| !params.is_array() && !params.is_object()) {
+		Some("Invalid Request: params must be an array or an object")
+	} else {
+		None
+	};
+	match (invalid, id) {
+		(Some(message), id) => Some(error(INVALID_REQUEST, message, id.unwrap_or(Value::Null))),
+		(None, None) => None,
+		(None, Some(id)) => Some(json!({"jsonrpc": "2.0", "result": object.get("params").cloned().unwrap_or(Value::Null), "id": id})),
+	}
+}
+
+fn respond(response: Option<Value>) -> HttpResponse {
+	match response {
+		Some(response) => HttpResponse::Ok()
+			.insert_header(header::ContentType(mime::APPLICATION_JSON))
+			.body(response.to_string()),
+		// nothing to answer when every request was a notification
+		None => HttpResponse::NoContent().finish(),
+	}
+}
+
+/// `POST /jsonrpc`: a JSON-RPC 2.0 server whose every method returns its params. Batches and
+/// notifications follow the spec, malformed calls get its error objects with HTTP 200.
+pub async fn jsonrpc(body: web::Bytes) -> HttpResponse {
+	let response = match serde_json::from_slice::<Value>(&body) {
+		Err(_) => Some(error(PARSE_ERROR, "Parse error", Value::Null)),
+		Ok(Value::Array(batch)) if batch.is_empty() => Some(error(INVALID_REQUEST, "Invalid Request: empty batch", Value::Null)),
+		Ok(Value::Array(batch)) => {
+			let responses: Vec<Value> = batch.iter().filter_map(call).collect();
+			(!responses.is_empty()).then_some(Value::Array(responses))
+		}
+		Ok(request) => call(&request),
+	};
+	respond(response)
+}
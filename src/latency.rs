@@ -0,0 +1,134 @@
+use actix_web::{
+	body::MessageBody,
+	dev::{ServiceRequest, ServiceResponse},
+	middleware::Next,
+	rt, web,
+};
+use anyhow::anyhow;
+use dashmap::DashMap;
+use hdrhistogram::Histogram;
+use log::*;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Length of the window the P99 is measured over, histograms start over after each.
+const WINDOW: Duration = Duration::from_secs(60);
+/// Slowest recordable latency in microseconds, slower requests count as this.
+const MAX_LATENCY_US: u64 = 3_600_000_000;
+
+/// Per-route latency histograms behind `--latency-alert-p99`.
+pub struct LatencyAlerts {
+	threshold: Duration,
+	routes: DashMap<String, Histogram<u64>>,
+}
+
+impl LatencyAlerts {
+	pub fn new(threshold: Duration) -> Self {
+		LatencyAlerts{threshold, routes: DashMap::new()}
+	}
+
+	fn observe(&self, route: String, elapsed: Duration) {
+		let mut histogram = self.routes.entry(route)
+			.or_insert_with(|| Histogram::new_with_bounds(1, MAX_LATENCY_US, 3).expect("valid histogram bounds"));
+		histogram.saturating_record(elapsed.as_micros().min(MAX_LATENCY_US as u128) as u64);
+	}
+
+	/// Warns about every route whose P99 over the closing window exceeds the threshold, then
+	/// starts the next window.
+	fn check(&self) {
+		for mut route in self.routes.iter_mut() {
+			if route.is_empty() {
+				continue;
+			}
+			let p99 = Duration::from_micros(route.value_at_quantile(0.99));
+			if p99 > self.threshold {
+				warn!("latency alert: {} p99 {:?} over {} requests in the last {:?} exceeds {:?}",
+					route.key(), p99, route.len(), WINDOW, self.threshold);
+			}
+			route.reset();
+		}
+	}
+
+	pub async fn watch(alerts: web::Data<LatencyAlerts>) {
+		let mut ticker = rt::time::interval_at(rt::time::Instant::now() + WINDOW, WINDOW);
+		loop {
+			ticker.tick().await;
+			alerts.check();
+		}
+	}
+}
+
+/// Middleware recording request latency per matched route, when alerts are enabled.
+pub async fn track(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let alerts = req.app_data::<web::Data<LatencyAlerts>>().cloned();
+	let start = Instant::now();
+	let res = next.call(req).await?;
+
+	if let Some(alerts) = alerts {
+		let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+		alerts.observe(route, start.elapsed());
+	}
+	Ok(res)
+}
+
+/// Which connections `--inject-latency` slows down, `--inject-latency-scope`.
+#[derive(Clone, Copy, Default)]
+pub enum LatencyScope {
+	#[default]
+	All,
+	Tls,
+	Plain,
+}
+
+impl FromStr for LatencyScope {
+	type Err = anyhow::Error;
+
+	fn from_str(scope: &str) -> anyhow::Result<Self> {
+		match scope {
+			"all" => Ok(LatencyScope::All),
+			"tls" => Ok(LatencyScope::Tls),
+			"plain" => Ok(LatencyScope::Plain),
+			_ => Err(anyhow!("unknown latency scope {}, expected all, tls or plain", scope)),
+		}
+	}
+}
+
+impl fmt::Display for LatencyScope {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			LatencyScope::All => "all",
+			LatencyScope::Tls => "TLS",
+			LatencyScope::Plain => "plain HTTP",
+		})
+	}
+}
+
+/// Fixed delay added before every request in scope is handled, `--inject-latency`.
+#[derive(Clone, Copy)]
+pub struct InjectedLatency {
+	pub delay: Duration,
+	pub scope: LatencyScope,
+}
+
+/// Middleware delaying requests by the injected latency, when their connection is in scope.
+/// TLS is told apart by the listener the request came in on, not by forwarded headers.
+pub async fn inject(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	if let Some(latency) = req.app_data::<InjectedLatency>().copied() {
+		let in_scope = match latency.scope {
+			LatencyScope::All => true,
+			LatencyScope::Tls => req.app_config().secure(),
+			LatencyScope::Plain => !req.app_config().secure(),
+		};
+		if in_scope {
+			rt::time::sleep(latency.delay).await;
+		}
+	}
+	next.call(req).await
+}
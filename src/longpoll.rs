@@ -0,0 +1,88 @@
+use actix_web::{http::header, rt, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::connection;
+use crate::error::BenchError;
+use crate::stats::{Stats, Tracked};
+
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+const MAX_TIMEOUT_MS: u64 = 300_000;
+
+/// Wakes the requests waiting on `/long-poll` when `POST /publish` is called.
+pub struct LongPoll {
+	events: broadcast::Sender<Arc<str>>,
+}
+
+impl Default for LongPoll {
+	fn default() -> Self {
+		// every waiter takes the first event only, one slot is enough
+		LongPoll{events: broadcast::channel(1).0}
+	}
+}
+
+/// The next published event. One that lagged behind later ones gives way to the latest, a
+/// waiter only wants to be woken. `None` once the sender is gone.
+async fn next_event(events: &mut broadcast::Receiver<Arc<str>>) -> Option<Arc<str>> {
+	loop {
+		match events.recv().await {
+			Ok(event) => return Some(event),
+			Err(broadcast::error::RecvError::Lagged(_)) => continue,
+			Err(broadcast::error::RecvError::Closed) => return None,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+pub struct LongPollQuery {
+	timeout_ms: Option<u64>,
+}
+
+/// `/long-poll?timeout_ms=<n>`: holds the request until the next `POST /publish`, answered
+/// with its body, or until the timeout, answered with 204. A client going away stops the
+/// wait early.
+pub async fn long_poll(req: HttpRequest, query: web::Query<LongPollQuery>, long_poll: web::Data<LongPoll>, stats: web::Data<Stats>) -> Result<HttpResponse, BenchError> {
+	let timeout = query.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS);
+	if timeout > MAX_TIMEOUT_MS {
+		return Err(BenchError::bad_request(format!("timeout_ms must be at most {}", MAX_TIMEOUT_MS)));
+	}
+
+	let mut events = long_poll.events.subscribe();
+	let waiting = Tracked::new(stats.clone(), |stats| &stats.long_poll.waiting);
+	let event = tokio::select! {
+		event = rt::time::timeout(Duration::from_millis(timeout), next_event(&mut events)) => event,
+		_ = connection::disconnected(&req) => {
+			stats.long_poll.disconnected.fetch_add(1, Ordering::Relaxed);
+			// nobody is left to read the response
+			return Ok(HttpResponse::NoContent().finish());
+		}
+	};
+	drop(waiting);
+
+	match event {
+		Ok(Some(event)) => {
+			stats.long_poll.delivered.fetch_add(1, Ordering::Relaxed);
+			Ok(HttpResponse::Ok()
+			.insert_header(header::ContentType(mime::APPLICATION_JSON))
+			.body(json!({"event": &*event}).to_string()))
+		}
+		Ok(None) => Ok(HttpResponse::NoContent().finish()),
+		Err(_) => {
+			stats.long_poll.timeouts.fetch_add(1, Ordering::Relaxed);
+			Ok(HttpResponse::NoContent().finish())
+		}
+	}
+}
+
+/// `POST /publish`: completes every waiting `/long-poll` with the request body.
+pub async fn publish(body: web::Bytes, long_poll: web::Data<LongPoll>) -> Result<HttpResponse, BenchError> {
+	let event = std::str::from_utf8(&body).map_err(|_| BenchError::bad_request("event must be UTF-8 text"))?;
+	let woken = long_poll.events.send(Arc::from(event)).unwrap_or(0);
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"woken": woken}).to_string()))
+}
@@ -0,0 +1,81 @@
+use actix_web::{http::header, web, HttpResponse};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+
+use crate::error::BenchError;
+
+const DEFAULT_WORDS: usize = 100;
+const MAX_WORDS: usize = 1_000_000;
+const OPENING: [&str; 5] = ["Lorem", "ipsum", "dolor", "sit", "amet"];
+const WORDS: [&str; 64] = [
+	"consectetur", "adipiscing", "elit", "sed", "do", "eiusmod", "tempor", "incididunt",
+	"ut", "labore", "et", "dolore", "magna", "aliqua", "enim", "ad",
+	"minim", "veniam", "quis", "nostrud", "exercitation", "ullamco", "laboris", "nisi",
+	"aliquip", "ex", "ea", "commodo", "consequat", "duis", "aute", "irure",
+	"in", "reprehenderit", "voluptate", "velit", "esse", "cillum", "eu", "fugiat",
+	"nulla", "pariatur", "excepteur", "sint", "occaecat", "cupidatat", "non", "proident",
+	"sunt", "culpa", "qui", "officia", "deserunt", "mollit", "anim", "id",
+	"est", "laborum", "lorem", "ipsum", "dolor", "sit", "amet", "vel",
+];
+const SENTENCE_WORDS: std::ops::RangeInclusive<usize> = 6..=16;
+const PARAGRAPH_SENTENCES: std::ops::RangeInclusive<usize> = 3..=7;
+
+#[derive(Deserialize)]
+pub struct TextQuery {
+	words: Option<usize>,
+	#[serde(default)]
+	seed: u64,
+}
+
+/// `words` words of lorem ipsum in sentences and paragraphs, always the same for a seed.
+fn lorem(words: usize, seed: u64) -> String {
+	let mut rng = StdRng::seed_from_u64(seed);
+	let mut text = String::with_capacity(words * 7);
+	let (mut sentence_left, mut paragraph_left) = (0, rng.gen_range(PARAGRAPH_SENTENCES));
+	let mut separator = "";
+	for index in 0..words {
+		let sentence_start = sentence_left == 0;
+		if sentence_start {
+			sentence_left = rng.gen_range(SENTENCE_WORDS);
+		}
+		let word = match OPENING.get(index) {
+			Some(word) => word,
+			None => WORDS[rng.gen_range(0..WORDS.len())],
+		};
+		text.push_str(separator);
+		separator = " ";
+		let mut chars = word.chars();
+		match chars.next() {
+			Some(first) if sentence_start => {
+				text.extend(first.to_uppercase());
+				text.push_str(chars.as_str());
+			}
+			_ => text.push_str(word),
+		}
+
+		sentence_left -= 1;
+		if sentence_left == 0 || index + 1 == words {
+			text.push('.');
+			paragraph_left -= 1;
+			if paragraph_left == 0 {
+				separator = "\n\n";
+				paragraph_left = rng.gen_range(PARAGRAPH_SENTENCES);
+			}
+		} else if rng.gen_ratio(1, 8) {
+			text.push(',');
+		}
+	}
+	text
+}
+
+/// `/text?words=<n>&seed=<s>`: `n` words of lorem ipsum as plain text, the same text for
+/// the same seed, default 0. Compresses like prose rather than like random bytes.
+pub async fn text(query: web::Query<TextQuery>) -> Result<HttpResponse, BenchError> {
+	let words = query.words.unwrap_or(DEFAULT_WORDS);
+	if words > MAX_WORDS {
+		return Err(BenchError::bad_request(format!("words must be at most {}", MAX_WORDS)));
+	}
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::TEXT_PLAIN_UTF_8))
+	.body(lorem(words, query.seed)))
+}
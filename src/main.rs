@@ -1,24 +1,81 @@
-// to create a self-signed temporary cert for testing: `openssl req -x509 -newkey rsa:4096 -nodes -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
-use actix_web::{web::{self}, App, HttpServer, HttpResponse, http::header};
+// to create a self-signed temporary cert for testing: `bench-server gen-cert`, or `openssl req -x509 -newkey rsa:4096 -nodes -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
+use actix_web::{web::{self}, App, HttpServer, HttpRequest, HttpResponse, http::header, middleware};
 #[cfg(feature="with_openssl")]
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
-#[cfg(feature="with_rustls")]
-use rustls::{Certificate, PrivateKey, ServerConfig};
-#[cfg(feature="with_rustls")]
-use rustls_pemfile::{certs, pkcs8_private_keys};
+use openssl::ssl::{SslAcceptor, SslMethod};
 
 use anyhow::{*, Result};
 use clap::{Arg,  Command};
-use {log::*, dotenv};
-use mime;
+use log::*;
+use std::collections::HashSet;
 
 const DEFAULT_IP : &str = "0.0.0.0";
 const DEFAULT_PORT : u16 = 3000;
 const DEFAULT_KEY_FILE : &str= "key.pem";
 const DEFAULT_CERT_FILE : &str= "cert.pem";
 const DEFAULT_CONNECTIONS : usize = 25*1024;
+const DEFAULT_MAX_BODY_SIZE : usize = 16*1024*1024;
+/// actix-tls default of concurrent TLS handshakes per worker.
+const DEFAULT_CONNECTION_RATE : usize = 256;
 
+mod admin;
+mod batch;
+mod cache_control;
+mod check;
+mod compare;
+mod connection;
+mod content_type;
+mod cors;
+mod cookies;
+mod disruption;
+mod download;
+mod echo;
+mod error;
+mod fanout;
+mod gen_cert;
+mod hash;
+mod headers;
+#[cfg(feature = "http3")]
+mod http3;
+mod http_signature;
+mod infinite;
+mod idempotency;
+mod info;
+mod jitter;
+mod json;
+mod jsonrpc;
+mod latency;
+mod method_override;
+mod longpoll;
+mod lorem;
+mod matrix;
+mod memory;
+mod metrics;
+mod prg;
+mod proxy;
+mod random;
+mod redirect;
 mod response;
+mod rlimit;
+mod sequence;
+mod server_timing;
+mod shutdown;
+mod signing;
+mod simulate;
+mod slow;
+mod socket;
+mod sse;
+mod stats;
+mod status;
+mod stress;
+mod tcp_echo;
+mod template;
+mod throughput;
+mod tls;
+mod trailing_slash;
+mod upload;
+mod watchdog;
+mod writes;
+mod ws;
 
 #[derive(Clone)]
 pub struct ResponseData {
@@ -30,7 +87,13 @@ pub struct ResponseData {
 }
 
 
+/// `--max-body-size`, for handlers that read the body as a stream rather than through
+/// the extractors `web::PayloadConfig` limits.
+#[derive(Clone, Copy)]
+pub struct MaxBodySize(pub usize);
+
 impl ResponseData {
+	#[allow(dead_code)]
 	fn new() -> Self {
 		ResponseData{
 			index: response::INDEX_RESPONSE.to_string(),
@@ -75,6 +138,9 @@ async fn main() -> Result<()> {
 						  .version("1.0")
 						  .author("Xu Haojie <xuhaojie@hotmail.com>")
 						  .about("A simple http(s) server for benchmark")
+						  .subcommand(gen_cert::command())
+						  .subcommand(compare::command())
+						  .subcommand(check::command())
 						  .arg(Arg::with_name("key")
 						  	.short('k')
 							.value_name("key")
@@ -85,11 +151,30 @@ async fn main() -> Result<()> {
 							.value_name("cert")
 							.takes_value(true)
 						  	.help("Certificate chain file, default cert.pem, env key: CERT_FILE"))
+ 						  .arg(Arg::with_name("client_ca")
+						  	.long("client-ca")
+							.value_name("ca file")
+							.takes_value(true)
+						  	.help("Require https clients to present a certificate issued by a CA in this PEM file, env key: CLIENT_CA_FILE"))
+ 						  .arg(Arg::with_name("sni_required")
+						  	.long("sni-required")
+						  	.help("Reject https clients that don't send a server name (SNI) with an unrecognized_name alert, env key: SNI_REQUIRED"))
+ 						  .arg(Arg::with_name("sni_hostname")
+						  	.long("sni-hostname")
+							.value_name("hostname")
+							.takes_value(true)
+						  	.help("Reject https clients asking for any other server name (SNI), implies --sni-required, env key: SNI_HOSTNAME"))
  						  .arg(Arg::with_name("ip")
 						  	.short('i')
 							.value_name("ip")
 							.takes_value(true)
 						  	.help("Server bind ip, default 0.0.0.0, env key: SERVER_IP"))
+						  .arg(Arg::with_name("bind_interface")
+						  	.long("bind-interface")
+							.value_name("ifname")
+							.takes_value(true)
+							.conflicts_with("ip")
+						  	.help("Bind to the address of this network interface instead of an ip, e.g. eth0, env key: BIND_INTERFACE"))
 						  .arg(Arg::with_name("port")
 						  	.short('p')
 							.value_name("http port")
@@ -101,19 +186,278 @@ async fn main() -> Result<()> {
 							.value_name("https port")
 							.takes_value(true)
 						  	.help("Enable and specify the https server port, env key: HTTPS_PORT"))
+						  .arg(Arg::with_name("http3")
+						  	.long("http3-port")
+							.value_name("http3 port")
+							.takes_value(true)
+						  	.help("Enable and specify the experimental HTTP/3 (QUIC) port, needs the http3 feature, env key: HTTP3_PORT"))
+						  .arg(Arg::with_name("tcp_echo")
+						  	.long("tcp-echo")
+							.value_name("port")
+							.takes_value(true)
+						  	.help("Also run a raw TCP echo server on this port, the transport baseline below HTTP, env key: TCP_ECHO_PORT"))
 						  .arg(Arg::with_name("workers")
 						  	.short('w')
 							.value_name("workers")
 							.takes_value(true)
 						  	.help("Workers, default cpu core number, env key: WORKERS"))
+						  .arg(Arg::with_name("workers_per_cpu")
+						  	.long("workers-per-cpu")
+							.value_name("factor")
+							.takes_value(true)
+							.conflicts_with("workers")
+						  	.help("Workers as a multiple of the cpu core number, rounded up, e.g. 0.5 or 2, env key: WORKERS_PER_CPU"))
 						  .arg(Arg::with_name("max_connections")
 						  	.short('m')
 							.value_name("max_connections")
 							.takes_value(true)
-						  	.help("Max connections, default 25k, env key: CONNECTIONS"));
+						  	.help("Max connections, default 25k, env key: CONNECTIONS"))
+						  .arg(Arg::with_name("raise_nofile")
+						  	.long("raise-nofile")
+						  	.help("Raise the open file soft limit to the hard limit at startup, env key: RAISE_NOFILE"))
+						  .arg(Arg::with_name("backlog")
+						  	.long("backlog")
+							.value_name("connections")
+							.takes_value(true)
+						  	.help("Listen backlog, connections waiting to be accepted, default 2048, env key: BACKLOG"))
+						  .arg(Arg::with_name("tcp_keepalive")
+						  	.long("tcp-keepalive")
+							.value_name("secs")
+							.takes_value(true)
+						  	.help("Enable SO_KEEPALIVE on accepted sockets, probing after this many idle seconds, default off, env key: TCP_KEEPALIVE"))
+						  .arg(Arg::with_name("overflow_strategy")
+						  	.long("overflow-strategy")
+							.value_name("refuse|accept-then-503")
+							.takes_value(true)
+						  	.help("Beyond max connections stop accepting, or accept and answer with 503 and Retry-After, default refuse, env key: OVERFLOW_STRATEGY"))
+						  .arg(Arg::with_name("max_connection_rate")
+						  	.long("max-connection-rate")
+							.value_name("handshakes")
+							.takes_value(true)
+						  	.help("Concurrent TLS handshakes per worker before it stops accepting, default 256, env key: MAX_CONNECTION_RATE"))
+						  .arg(Arg::with_name("so_sndbuf")
+						  	.long("so-sndbuf")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("SO_SNDBUF of the listening sockets, default system, env key: SO_SNDBUF"))
+						  .arg(Arg::with_name("so_rcvbuf")
+						  	.long("so-rcvbuf")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("SO_RCVBUF of the listening sockets, default system, env key: SO_RCVBUF"))
+						  .arg(Arg::with_name("socket_buffer_size")
+						  	.long("socket-buffer-size")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("Both SO_SNDBUF and SO_RCVBUF, --so-sndbuf/--so-rcvbuf take precedence, env key: SOCKET_BUFFER_SIZE"))
+						  .arg(Arg::with_name("shutdown_hook")
+						  	.long("shutdown-hook")
+							.value_name("command")
+							.takes_value(true)
+						  	.help("Shell command run on shutdown with the final stats as JSON on stdin, env key: SHUTDOWN_HOOK"))
+						  .arg(Arg::with_name("shutdown_mode")
+						  	.long("shutdown-mode")
+							.value_name("graceful|immediate")
+							.takes_value(true)
+						  	.help("On SIGINT/SIGTERM drain in-flight requests or drop all connections, default graceful, env key: SHUTDOWN_MODE"))
+						  .arg(Arg::with_name("allow_method_override")
+						  	.long("allow-method-override")
+						  	.help("Dispatch POST requests as the PUT/DELETE/PATCH named in X-HTTP-Method-Override, env key: ALLOW_METHOD_OVERRIDE"))
+						  .arg(Arg::with_name("allow_disruption")
+						  	.long("allow-disruption")
+						  	.help("Enable fault injection endpoints that break connections, /reset-connection and /simulate-timeout, env key: ALLOW_DISRUPTION"))
+						  .arg(Arg::with_name("strict_json")
+						  	.long("strict-json")
+						  	.help("Reject POST/PUT/PATCH requests whose non-empty body isn't valid JSON with a 400, env key: STRICT_JSON"))
+						  .arg(Arg::with_name("verify_http_signature")
+						  	.long("verify-http-signature")
+							.value_name("public-key-file")
+							.takes_value(true)
+						  	.help("Reject requests without a valid HTTP message signature by this ed25519/RSA PEM key, env key: VERIFY_HTTP_SIGNATURE"))
+						  .arg(Arg::with_name("hmac_key")
+						  	.long("hmac-key")
+							.value_name("hex")
+							.takes_value(true)
+						  	.help("HMAC-SHA256 key of /sign and /verify-signature, env key: HMAC_KEY"))
+						  .arg(Arg::with_name("idempotency_key_ttl")
+						  	.long("idempotency-key-ttl")
+							.value_name("secs")
+							.takes_value(true)
+						  	.help("How long POST/PATCH responses are replayed for a repeated Idempotency-Key, default 300, env key: IDEMPOTENCY_KEY_TTL"))
+						  .arg(Arg::with_name("server_timing")
+						  	.long("server-timing")
+						  	.help("Add a Server-Timing header with the recv, handler and serialize phases to every response, env key: SERVER_TIMING"))
+						  .arg(Arg::with_name("h2_max_streams")
+						  	.long("h2-max-streams")
+							.value_name("streams")
+							.takes_value(true)
+						  	.help("Concurrent requests handled per HTTP/2 connection, extra streams wait for a slot, default unlimited, env key: H2_MAX_STREAMS"))
+						  .arg(Arg::with_name("startup_delay")
+						  	.long("startup-delay")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("Wait this long before binding the listeners, to test client reconnects during restarts, env key: STARTUP_DELAY"))
+						  .arg(Arg::with_name("trace_connections")
+						  	.long("trace-connections")
+						  	.help("Log accept, TLS handshake, request and close events of every connection, env key: TRACE_CONNECTIONS"))
+						  .arg(Arg::with_name("admin_token")
+						  	.long("admin-token")
+							.value_name("token")
+							.takes_value(true)
+						  	.help("Bearer token required by the /admin/ routes, also enables /admin/endpoints, env key: ADMIN_TOKEN"))
+						  .arg(Arg::with_name("disable_route")
+						  	.long("disable-route")
+							.value_name("path")
+							.takes_value(true)
+							.multiple_occurrences(true)
+						  	.help("Don't register the route at this path, repeatable, env key: DISABLED_ROUTES (comma separated)"))
+						  .arg(Arg::with_name("upstream")
+						  	.long("upstream")
+							.value_name("url")
+							.takes_value(true)
+						  	.help("Reverse proxy requests under the proxy prefix to this url, env key: UPSTREAM"))
+						  .arg(Arg::with_name("proxy_prefix")
+						  	.long("proxy-prefix")
+							.value_name("path")
+							.takes_value(true)
+						  	.help("Path prefix forwarded to the upstream, default /proxy-pass, env key: PROXY_PREFIX"))
+						  .arg(Arg::with_name("max_body_size")
+						  	.long("max-body-size")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("Largest request body buffered by handlers, default 16MiB, env key: MAX_BODY_SIZE"))
+						  .arg(Arg::with_name("content_type")
+						  	.long("content-type")
+							.value_name("path=mime")
+							.takes_value(true)
+							.multiple_occurrences(true)
+						  	.help("Content-Type of the responses at this path, e.g. /get=application/xml, repeatable, env key: CONTENT_TYPES (comma separated)"))
+						  .arg(Arg::with_name("allow_origin")
+						  	.long("allow-origin")
+							.value_name("origin")
+							.takes_value(true)
+							.multiple_occurrences(true)
+						  	.help("Let this origin, e.g. https://app.example, make cross-origin requests with credentials, repeatable, env key: ALLOW_ORIGINS (comma separated)"))
+						  .arg(Arg::with_name("matrix_max_delay")
+						  	.long("matrix-max-delay")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("Largest delay_ms /benchmark-matrix accepts, default 60000, env key: MATRIX_MAX_DELAY"))
+						  .arg(Arg::with_name("matrix_max_size")
+						  	.long("matrix-max-size")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("Largest size_bytes /benchmark-matrix accepts, default 16MiB, env key: MATRIX_MAX_SIZE"))
+						  .arg(Arg::with_name("cache_control")
+						  	.long("cache-control")
+							.value_name("value")
+							.takes_value(true)
+						  	.help("Cache-Control of responses that don't set their own, e.g. \"public, max-age=60\", default none, env key: CACHE_CONTROL"))
+						  .arg(Arg::with_name("cache_control_path")
+						  	.long("cache-control-path")
+							.value_name("path=value")
+							.takes_value(true)
+							.multiple_occurrences(true)
+						  	.help("Cache-Control of the responses at this path, e.g. /get=no-store, repeatable, env key: CACHE_CONTROL_PATHS (semicolon separated)"))
+						  .arg(Arg::with_name("status_body")
+						  	.long("status-body")
+							.value_name("code=@file|inline")
+							.takes_value(true)
+							.multiple_occurrences(true)
+						  	.help("Body of /status/{code} and of error responses with this status, @ reads it from a file, repeatable, env key: STATUS_BODIES (semicolon separated)"))
+						  .arg(Arg::with_name("body_template")
+						  	.long("body-template")
+							.value_name("file")
+							.takes_value(true)
+						  	.help("Handlebars template rendered per request by /template, e.g. body.json.hbs, env key: BODY_TEMPLATE"))
+						  .arg(Arg::with_name("sse_file")
+						  	.long("sse-file")
+							.value_name("file")
+							.takes_value(true)
+						  	.help("Recorded SSE stream /sse-replay plays back, blank line separated events, env key: SSE_FILE"))
+						  .arg(Arg::with_name("memory_limit")
+						  	.long("memory-limit")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("Answer new requests with 503 while the resident memory is above this, Linux only, env key: MEMORY_LIMIT"))
+						  .arg(Arg::with_name("memory_check_interval")
+						  	.long("memory-check-interval")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("How often the resident memory is checked against --memory-limit, default 1000, env key: MEMORY_CHECK_INTERVAL"))
+						  .arg(Arg::with_name("watchdog_interval")
+						  	.long("watchdog-interval")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("How often every worker checks its event loop isn't stalled, 0 turns it off, default 100, env key: WATCHDOG_INTERVAL"))
+						  .arg(Arg::with_name("watchdog_threshold")
+						  	.long("watchdog-threshold")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("Log a warning when a worker's check runs this much late, default 50, env key: WATCHDOG_THRESHOLD"))
+						  .arg(Arg::with_name("max_batch_size")
+						  	.long("max-batch-size")
+							.value_name("requests")
+							.takes_value(true)
+						  	.help("Most sub-requests a POST /batch may carry, default 100, env key: MAX_BATCH_SIZE"))
+						  .arg(Arg::with_name("max_request_header_size")
+						  	.long("max-request-header-size")
+							.value_name("bytes")
+							.takes_value(true)
+						  	.help("Reject requests whose headers take more bytes with 431, env key: MAX_REQUEST_HEADER_SIZE"))
+						  .arg(Arg::with_name("max_redirects")
+						  	.long("max-redirects")
+							.value_name("hops")
+							.takes_value(true)
+						  	.help("Longest redirect chain clients may request, default 20, env key: MAX_REDIRECTS"))
+						  .arg(Arg::with_name("latency_alert_p99")
+						  	.long("latency-alert-p99")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("Warn when a route's P99 latency over the last 60s exceeds this, env key: LATENCY_ALERT_P99"))
+						  .arg(Arg::with_name("inject_latency")
+						  	.long("inject-latency")
+							.value_name("ms")
+							.takes_value(true)
+						  	.help("Delay every request in the latency scope by this much, env key: INJECT_LATENCY"))
+						  .arg(Arg::with_name("inject_latency_scope")
+						  	.long("inject-latency-scope")
+							.value_name("all|tls|plain")
+							.takes_value(true)
+						  	.help("Connections --inject-latency applies to, default all, env key: INJECT_LATENCY_SCOPE"))
+						  .arg(Arg::with_name("trailing_slash")
+						  	.long("trailing-slash")
+							.value_name("strict|merge|redirect")
+							.takes_value(true)
+						  	.help("Paths with a trailing slash are distinct, routed like the path without, or redirected there, default strict, env key: TRAILING_SLASH"))
+						  .arg(Arg::with_name("tokio_console")
+						  	.long("tokio-console")
+						  	.help("Serve runtime diagnostics to tokio-console on port 6669, needs the console feature, env key: TOKIO_CONSOLE"));
 
 	let matches = cmd.get_matches();
 
+	if let Some(("gen-cert", gen_cert_matches)) = matches.subcommand() {
+		return gen_cert::run(gen_cert_matches);
+	}
+	if let Some(("compare", compare_matches)) = matches.subcommand() {
+		return compare::run(compare_matches);
+	}
+
+	let tokio_console = matches.is_present("tokio_console") || match dotenv::var("TOKIO_CONSOLE") {
+		dotenv::Result::Ok(console) => console.parse::<bool>()?,
+		_ => false,
+	};
+	if tokio_console {
+		// as early as possible, tasks spawned before are never seen by the console
+		#[cfg(feature = "console")]
+		{
+			console_subscriber::init();
+			info!("tokio-console server listen on 127.0.0.1:6669");
+		}
+		#[cfg(not(feature = "console"))]
+		bail!("--tokio-console needs a build with the console feature");
+	}
+
 	let key_file_name = match matches.value_of("key"){
 		Some(file) => file.to_string(),
 		_ => match dotenv::var("KEY_FILE") {
@@ -130,8 +474,35 @@ async fn main() -> Result<()> {
 		}
 	};
 
-	let server_ip = match matches.value_of("ip"){
-		Some(ip) => ip.to_string(),
+	let client_ca_file_name = match matches.value_of("client_ca"){
+		Some(file) => Some(file.to_string()),
+		_ => dotenv::var("CLIENT_CA_FILE").ok(),
+	};
+
+	let sni_policy = tls::SniPolicy{
+		required: matches.is_present("sni_required") || match dotenv::var("SNI_REQUIRED") {
+			dotenv::Result::Ok(required) => required.parse::<bool>()?,
+			_ => false,
+		},
+		hostname: match matches.value_of("sni_hostname"){
+			Some(hostname) => Some(hostname.to_string()),
+			_ => dotenv::var("SNI_HOSTNAME").ok(),
+		},
+	};
+
+	let bind_interface = match matches.value_of("bind_interface"){
+		Some(name) => Some(name.to_string()),
+		_ => dotenv::var("BIND_INTERFACE").ok(),
+	};
+
+	let server_ip = match (matches.value_of("ip"), bind_interface) {
+		(Some(ip), _) => ip.to_string(),
+		(_, Some(name)) => {
+			let ip = socket::interface_ip(&name).with_context(|| format!("failed to resolve interface {}", name))?;
+			info!("interface {} resolved to {}", name, ip);
+			// the ports are appended as ip:port
+			if ip.is_ipv6() { format!("[{}]", ip) } else { ip.to_string() }
+		}
 		_ => match dotenv::var("SERVER_IP") {
 			dotenv::Result::Ok(ip) => ip,
 			_ => DEFAULT_IP.to_string(),
@@ -154,6 +525,26 @@ async fn main() -> Result<()> {
 		}
 	};
 
+	let http3_port = match matches.value_of("http3"){
+		Some(port) => port.parse::<u16>()?,
+		_ => match dotenv::var("HTTP3_PORT") {
+			dotenv::Result::Ok(port) => port.parse::<u16>()?,
+			_ => 0u16,
+		}
+	};
+	#[cfg(not(feature = "http3"))]
+	if http3_port != 0 {
+		bail!("--http3-port needs a build with the http3 feature");
+	}
+
+	let tcp_echo_port = match matches.value_of("tcp_echo"){
+		Some(port) => port.parse::<u16>()?,
+		_ => match dotenv::var("TCP_ECHO_PORT") {
+			dotenv::Result::Ok(port) => port.parse::<u16>()?,
+			_ => 0u16,
+		}
+	};
+
 	let workers = match matches.value_of("workers"){
 		Some(workers) => workers.parse::<usize>()?,
 		_ => match dotenv::var("WORKERS") {
@@ -162,6 +553,26 @@ async fn main() -> Result<()> {
 		}
 	};
 
+	let workers_per_cpu = match matches.value_of("workers_per_cpu"){
+		Some(factor) => Some(factor.parse::<f64>()?),
+		_ => match dotenv::var("WORKERS_PER_CPU") {
+			dotenv::Result::Ok(factor) => Some(factor.parse::<f64>()?),
+			_ => None,
+		}
+	};
+
+	let workers = match workers_per_cpu {
+		None => workers,
+		Some(_) if workers > 0 => bail!("--workers and --workers-per-cpu are mutually exclusive"),
+		Some(factor) if !(factor.is_finite() && factor > 0.0) => bail!("--workers-per-cpu must be a positive number"),
+		Some(factor) => {
+			let cpus = num_cpus::get();
+			let workers = ((cpus as f64 * factor).ceil() as usize).max(1);
+			info!("{} workers for {} cpus at {} per cpu", workers, cpus, factor);
+			workers
+		}
+	};
+
 	let connections = match matches.value_of("max_connections"){
 		Some(connections) => connections.parse::<usize>()?,
 		_ => match dotenv::var("CONNECTIONS") {
@@ -170,70 +581,739 @@ async fn main() -> Result<()> {
 		}
 	};
 
+	let overflow_strategy = match matches.value_of("overflow_strategy"){
+		Some(strategy) => strategy.parse::<connection::OverflowStrategy>()?,
+		_ => match dotenv::var("OVERFLOW_STRATEGY") {
+			dotenv::Result::Ok(strategy) => strategy.parse::<connection::OverflowStrategy>()?,
+			_ => connection::OverflowStrategy::default(),
+		}
+	};
+	if overflow_strategy == connection::OverflowStrategy::AcceptThen503 && connections == 0 {
+		bail!("--overflow-strategy accept-then-503 needs a --max-connections limit");
+	}
+	info!("connections beyond the limit: {}", overflow_strategy);
+	// under accept-then-503 the limit is enforced per connection by connection::reject_overflow
+	let accept_limit = match overflow_strategy {
+		connection::OverflowStrategy::Refuse => connections,
+		connection::OverflowStrategy::AcceptThen503 => usize::MAX,
+	};
+
+	let socket_buffer = match matches.value_of("socket_buffer_size"){
+		Some(size) => Some(size.parse::<usize>()?),
+		_ => match dotenv::var("SOCKET_BUFFER_SIZE") {
+			dotenv::Result::Ok(size) => Some(size.parse::<usize>()?),
+			_ => None,
+		}
+	};
+
+	let send_buffer = match matches.value_of("so_sndbuf"){
+		Some(size) => Some(size.parse::<usize>()?),
+		_ => match dotenv::var("SO_SNDBUF") {
+			dotenv::Result::Ok(size) => Some(size.parse::<usize>()?),
+			_ => socket_buffer,
+		}
+	};
+
+	let recv_buffer = match matches.value_of("so_rcvbuf"){
+		Some(size) => Some(size.parse::<usize>()?),
+		_ => match dotenv::var("SO_RCVBUF") {
+			dotenv::Result::Ok(size) => Some(size.parse::<usize>()?),
+			_ => socket_buffer,
+		}
+	};
+
+	let raise_nofile = matches.is_present("raise_nofile") || match dotenv::var("RAISE_NOFILE") {
+		dotenv::Result::Ok(raise) => raise.parse::<bool>()?,
+		_ => false,
+	};
+	rlimit::check_nofile(connections, raise_nofile);
+
+	let backlog = match matches.value_of("backlog"){
+		Some(backlog) => Some(backlog.parse::<i32>()?),
+		_ => match dotenv::var("BACKLOG") {
+			dotenv::Result::Ok(backlog) => Some(backlog.parse::<i32>()?),
+			_ => None,
+		}
+	};
+
+	let connection_rate = match matches.value_of("max_connection_rate"){
+		Some(rate) => rate.parse::<usize>()?,
+		_ => match dotenv::var("MAX_CONNECTION_RATE") {
+			dotenv::Result::Ok(rate) => rate.parse::<usize>()?,
+			_ => DEFAULT_CONNECTION_RATE,
+		}
+	};
+
+	let keepalive = match matches.value_of("tcp_keepalive"){
+		Some(secs) => Some(secs.parse::<u64>()?),
+		_ => match dotenv::var("TCP_KEEPALIVE") {
+			dotenv::Result::Ok(secs) => Some(secs.parse::<u64>()?),
+			_ => None,
+		}
+	};
+	if keepalive == Some(0) {
+		bail!("--tcp-keepalive must be at least 1 second");
+	}
+	let keepalive = keepalive.map(std::time::Duration::from_secs);
+
+	let socket_options = socket::SocketOptions{send_buffer, recv_buffer, backlog, keepalive};
+
+	let shutdown_hook = match matches.value_of("shutdown_hook"){
+		Some(command) => Some(command.to_string()),
+		_ => dotenv::var("SHUTDOWN_HOOK").ok(),
+	};
+
+	let shutdown_mode = match matches.value_of("shutdown_mode"){
+		Some(mode) => mode.parse::<shutdown::ShutdownMode>()?,
+		_ => match dotenv::var("SHUTDOWN_MODE") {
+			dotenv::Result::Ok(mode) => mode.parse::<shutdown::ShutdownMode>()?,
+			_ => shutdown::ShutdownMode::default(),
+		}
+	};
+
+	let allow_method_override = matches.is_present("allow_method_override") || match dotenv::var("ALLOW_METHOD_OVERRIDE") {
+		dotenv::Result::Ok(allow) => allow.parse::<bool>()?,
+		_ => false,
+	};
+
+	let allow_disruption = matches.is_present("allow_disruption") || match dotenv::var("ALLOW_DISRUPTION") {
+		dotenv::Result::Ok(allow) => allow.parse::<bool>()?,
+		_ => false,
+	};
+	if allow_disruption {
+		warn!("fault injection endpoints are enabled, /reset-connection and /simulate-timeout reset connections on request");
+	}
+
+	let strict_json = matches.is_present("strict_json") || match dotenv::var("STRICT_JSON") {
+		dotenv::Result::Ok(strict) => strict.parse::<bool>()?,
+		_ => false,
+	};
+
+	let h2_max_streams = match matches.value_of("h2_max_streams"){
+		Some(streams) => Some(streams.parse::<usize>()?),
+		_ => match dotenv::var("H2_MAX_STREAMS") {
+			dotenv::Result::Ok(streams) => Some(streams.parse::<usize>()?),
+			_ => None,
+		}
+	};
+	match h2_max_streams {
+		Some(0) => bail!("--h2-max-streams must be at least 1"),
+		Some(streams) => info!("HTTP/2 connections handle at most {} concurrent streams", streams),
+		None => {}
+	}
+
+	let startup_delay = match matches.value_of("startup_delay"){
+		Some(ms) => Some(ms.parse::<u64>()?),
+		_ => match dotenv::var("STARTUP_DELAY") {
+			dotenv::Result::Ok(ms) => Some(ms.parse::<u64>()?),
+			_ => None,
+		}
+	}.map(std::time::Duration::from_millis);
+
+	let server_timing = matches.is_present("server_timing") || match dotenv::var("SERVER_TIMING") {
+		dotenv::Result::Ok(enabled) => enabled.parse::<bool>()?,
+		_ => false,
+	};
+
+	let trace_connections = matches.is_present("trace_connections") || match dotenv::var("TRACE_CONNECTIONS") {
+		dotenv::Result::Ok(trace) => trace.parse::<bool>()?,
+		_ => false,
+	};
+
+	let disabled_routes: std::sync::Arc<HashSet<String>> = std::sync::Arc::new(match matches.values_of("disable_route"){
+		Some(paths) => paths.map(str::to_string).collect(),
+		_ => match dotenv::var("DISABLED_ROUTES") {
+			dotenv::Result::Ok(paths) => paths.split(',').map(|path| path.trim().to_string()).filter(|path| !path.is_empty()).collect(),
+			_ => HashSet::new(),
+		}
+	});
+	if !disabled_routes.is_empty() {
+		info!("disabled routes: {:?}", disabled_routes);
+	}
+	let endpoints = web::Data::new(admin::Endpoints::new(routes().into_iter().map(|(path, _)| path).filter(|path| !disabled_routes.contains(*path))));
+
+	let admin_token = match matches.value_of("admin_token"){
+		Some(token) => Some(token.to_string()),
+		_ => dotenv::var("ADMIN_TOKEN").ok(),
+	}.map(|token| web::Data::new(admin::AdminToken(token)));
+	if admin_token.is_some() {
+		info!("admin routes require the admin token");
+	}
+
+	let max_body_size = match matches.value_of("max_body_size"){
+		Some(size) => size.parse::<usize>()?,
+		_ => match dotenv::var("MAX_BODY_SIZE") {
+			dotenv::Result::Ok(size) => size.parse::<usize>()?,
+			_ => DEFAULT_MAX_BODY_SIZE,
+		}
+	};
+
+	let content_types = match matches.values_of("content_type"){
+		Some(specs) => content_type::ContentTypes::parse(specs)?,
+		_ => match dotenv::var("CONTENT_TYPES") {
+			dotenv::Result::Ok(specs) => content_type::ContentTypes::parse(specs.split(',').map(str::trim).filter(|spec| !spec.is_empty()))?,
+			_ => content_type::ContentTypes::parse([])?,
+		}
+	};
+	let content_types = (!content_types.is_empty()).then(|| web::Data::new(content_types));
+
+	let allowed_origins = match matches.values_of("allow_origin"){
+		Some(origins) => cors::AllowedOrigins::parse(origins)?,
+		_ => match dotenv::var("ALLOW_ORIGINS") {
+			dotenv::Result::Ok(origins) => cors::AllowedOrigins::parse(origins.split(',').map(str::trim).filter(|origin| !origin.is_empty()))?,
+			_ => cors::AllowedOrigins::parse([])?,
+		}
+	};
+	let allowed_origins = (!allowed_origins.is_empty()).then(|| web::Data::new(allowed_origins));
+
+	let matrix_limits = matrix::MatrixLimits{
+		max_delay_ms: match matches.value_of("matrix_max_delay"){
+			Some(ms) => ms.parse::<u64>()?,
+			_ => match dotenv::var("MATRIX_MAX_DELAY") {
+				dotenv::Result::Ok(ms) => ms.parse::<u64>()?,
+				_ => matrix::DEFAULT_MAX_DELAY_MS,
+			}
+		},
+		max_size: match matches.value_of("matrix_max_size"){
+			Some(size) => size.parse::<usize>()?,
+			_ => match dotenv::var("MATRIX_MAX_SIZE") {
+				dotenv::Result::Ok(size) => size.parse::<usize>()?,
+				_ => matrix::DEFAULT_MAX_SIZE,
+			}
+		},
+	};
+
+	let cache_control_default = match matches.value_of("cache_control"){
+		Some(value) => Some(value.to_string()),
+		_ => dotenv::var("CACHE_CONTROL").ok(),
+	};
+	// directives are comma separated, hence the semicolons
+	let cache_control = match matches.values_of("cache_control_path"){
+		Some(specs) => cache_control::CacheControl::parse(cache_control_default.as_deref(), specs)?,
+		_ => match dotenv::var("CACHE_CONTROL_PATHS") {
+			dotenv::Result::Ok(specs) => cache_control::CacheControl::parse(cache_control_default.as_deref(), specs.split(';').map(str::trim).filter(|spec| !spec.is_empty()))?,
+			_ => cache_control::CacheControl::parse(cache_control_default.as_deref(), [])?,
+		}
+	};
+	let cache_control = (!cache_control.is_empty()).then(|| web::Data::new(cache_control));
+
+	let status_bodies = match matches.values_of("status_body"){
+		Some(specs) => status::StatusBodies::parse(specs)?,
+		_ => match dotenv::var("STATUS_BODIES") {
+			dotenv::Result::Ok(specs) => status::StatusBodies::parse(specs.split(';').map(str::trim).filter(|spec| !spec.is_empty()))?,
+			_ => status::StatusBodies::default(),
+		}
+	};
+	let status_bodies = (!status_bodies.is_empty()).then(|| web::Data::new(status_bodies));
+
+	let body_template = match matches.value_of("body_template"){
+		Some(file_name) => Some(file_name.to_string()),
+		_ => dotenv::var("BODY_TEMPLATE").ok(),
+	};
+	let body_template = match &body_template {
+		Some(file_name) => Some(web::Data::new(template::BodyTemplate::load(file_name)?)),
+		_ => None,
+	};
+
+	let sse_replay = match matches.value_of("sse_file"){
+		Some(file_name) => Some(file_name.to_string()),
+		_ => dotenv::var("SSE_FILE").ok(),
+	};
+	let sse_replay = match &sse_replay {
+		Some(file_name) => Some(web::Data::new(sse::SseReplay::load(file_name)?)),
+		_ => None,
+	};
+
+	let memory_limit = match matches.value_of("memory_limit"){
+		Some(bytes) => Some(bytes.parse::<u64>()?),
+		_ => match dotenv::var("MEMORY_LIMIT") {
+			dotenv::Result::Ok(bytes) => Some(bytes.parse::<u64>()?),
+			_ => None,
+		}
+	};
+	let memory_check_interval = std::time::Duration::from_millis(match matches.value_of("memory_check_interval"){
+		Some(ms) => ms.parse::<u64>()?,
+		_ => match dotenv::var("MEMORY_CHECK_INTERVAL") {
+			dotenv::Result::Ok(ms) => ms.parse::<u64>()?,
+			_ => memory::DEFAULT_CHECK_INTERVAL_MS,
+		}
+	});
+	if memory_limit.is_some() {
+		if memory_check_interval.is_zero() {
+			bail!("--memory-check-interval must be above 0");
+		}
+		memory::resident_bytes().context("--memory-limit can't be enforced")?;
+	}
+	let memory_guard = memory_limit.map(|limit| web::Data::new(memory::MemoryGuard::new(limit)));
+
+	let watchdog = watchdog::Watchdog{
+		interval: std::time::Duration::from_millis(match matches.value_of("watchdog_interval"){
+			Some(ms) => ms.parse::<u64>()?,
+			_ => match dotenv::var("WATCHDOG_INTERVAL") {
+				dotenv::Result::Ok(ms) => ms.parse::<u64>()?,
+				_ => watchdog::DEFAULT_INTERVAL_MS,
+			}
+		}),
+		threshold: std::time::Duration::from_millis(match matches.value_of("watchdog_threshold"){
+			Some(ms) => ms.parse::<u64>()?,
+			_ => match dotenv::var("WATCHDOG_THRESHOLD") {
+				dotenv::Result::Ok(ms) => ms.parse::<u64>()?,
+				_ => watchdog::DEFAULT_THRESHOLD_MS,
+			}
+		}),
+	};
+
+	let max_batch_size = match matches.value_of("max_batch_size"){
+		Some(size) => size.parse::<usize>()?,
+		_ => match dotenv::var("MAX_BATCH_SIZE") {
+			dotenv::Result::Ok(size) => size.parse::<usize>()?,
+			_ => batch::DEFAULT_MAX_BATCH_SIZE,
+		}
+	};
+
+	let request_header_limit = match matches.value_of("max_request_header_size"){
+		Some(size) => Some(size.parse::<usize>()?),
+		_ => match dotenv::var("MAX_REQUEST_HEADER_SIZE") {
+			dotenv::Result::Ok(size) => Some(size.parse::<usize>()?),
+			_ => None,
+		}
+	}.map(headers::RequestHeaderLimit);
+
+	let max_redirects = match matches.value_of("max_redirects"){
+		Some(hops) => hops.parse::<usize>()?,
+		_ => match dotenv::var("MAX_REDIRECTS") {
+			dotenv::Result::Ok(hops) => hops.parse::<usize>()?,
+			_ => redirect::DEFAULT_MAX_REDIRECTS,
+		}
+	};
+
+	let latency_alerts = match matches.value_of("latency_alert_p99"){
+		Some(ms) => Some(ms.parse::<u64>()?),
+		_ => match dotenv::var("LATENCY_ALERT_P99") {
+			dotenv::Result::Ok(ms) => Some(ms.parse::<u64>()?),
+			_ => None,
+		}
+	}.map(|ms| {
+		info!("alert on route p99 latency above {}ms", ms);
+		web::Data::new(latency::LatencyAlerts::new(std::time::Duration::from_millis(ms)))
+	});
+	if let Some(alerts) = &latency_alerts {
+		actix_web::rt::spawn(latency::LatencyAlerts::watch(alerts.clone()));
+	}
+
+	let latency_scope = match matches.value_of("inject_latency_scope"){
+		Some(scope) => scope.parse::<latency::LatencyScope>()?,
+		_ => match dotenv::var("INJECT_LATENCY_SCOPE") {
+			dotenv::Result::Ok(scope) => scope.parse::<latency::LatencyScope>()?,
+			_ => latency::LatencyScope::default(),
+		}
+	};
+
+	let trailing_slash = match matches.value_of("trailing_slash"){
+		Some(mode) => mode.parse::<trailing_slash::TrailingSlash>()?,
+		_ => match dotenv::var("TRAILING_SLASH") {
+			dotenv::Result::Ok(mode) => mode.parse::<trailing_slash::TrailingSlash>()?,
+			_ => trailing_slash::TrailingSlash::default(),
+		}
+	};
+	info!("trailing slash mode: {}", trailing_slash);
+
+	let injected_latency = match matches.value_of("inject_latency"){
+		Some(ms) => Some(ms.parse::<u64>()?),
+		_ => match dotenv::var("INJECT_LATENCY") {
+			dotenv::Result::Ok(ms) => Some(ms.parse::<u64>()?),
+			_ => None,
+		}
+	}.map(|ms| {
+		info!("injecting {}ms latency into {} requests", ms, latency_scope);
+		latency::InjectedLatency{delay: std::time::Duration::from_millis(ms), scope: latency_scope}
+	});
+
+	let proxy_prefix = match matches.value_of("proxy_prefix"){
+		Some(prefix) => prefix.to_string(),
+		_ => match dotenv::var("PROXY_PREFIX") {
+			dotenv::Result::Ok(prefix) => prefix,
+			_ => proxy::DEFAULT_PREFIX.to_string(),
+		}
+	};
+
+	let proxy = match matches.value_of("upstream"){
+		Some(upstream) => Some(upstream.to_string()),
+		_ => dotenv::var("UPSTREAM").ok(),
+	}.map(|upstream| {
+		info!("proxying {} to {}", proxy_prefix, upstream);
+		proxy::Proxy::new(&upstream, &proxy_prefix)
+	}).transpose()?.map(web::Data::new);
+
+	let signature_verifier = match matches.value_of("verify_http_signature"){
+		Some(file) => Some(file.to_string()),
+		_ => dotenv::var("VERIFY_HTTP_SIGNATURE").ok(),
+	}.map(|file| http_signature::SignatureVerifier::load(&file)).transpose()?.map(web::Data::new);
+
+	let hmac_key = match matches.value_of("hmac_key"){
+		Some(key) => Some(key.to_string()),
+		_ => dotenv::var("HMAC_KEY").ok(),
+	}.map(|key| signing::HmacKey::parse(&key)).transpose()?.map(|key| {
+		info!("HMAC signing key id {}", key.id());
+		web::Data::new(key)
+	});
+
+	let idempotency_ttl = match matches.value_of("idempotency_key_ttl"){
+		Some(secs) => std::time::Duration::from_secs(secs.parse::<u64>()?),
+		_ => match dotenv::var("IDEMPOTENCY_KEY_TTL") {
+			dotenv::Result::Ok(secs) => std::time::Duration::from_secs(secs.parse::<u64>()?),
+			_ => idempotency::DEFAULT_TTL,
+		}
+	};
+	let idempotency = web::Data::new(idempotency::Idempotency::new(idempotency_ttl));
+
 	let response_data =web::Data::new(ResponseData::load());
+	let metrics = web::Data::new(metrics::Metrics::new());
+	let stats = web::Data::new(stats::Stats::default());
+	let multi_status = web::Data::new(status::MultiStatus::default());
+	let sequence = web::Data::new(sequence::Sequence::default());
+	let broadcasts = web::Data::new(sse::Broadcasts::default());
+	let long_poll = web::Data::new(longpoll::LongPoll::default());
+	let concurrent_writes = web::Data::new(writes::ConcurrentWrites::default());
+	let post_redirect_get = web::Data::new(prg::PostRedirectGet::default());
+	let warmup = web::Data::new(slow::Warmup::default());
+	let batch = web::Data::new(batch::Batch::default());
+	let batch_registry = batch.clone();
+	let listeners = web::Data::new(admin::Listeners::default());
+	let listener_registry = listeners.clone();
+	let memory_monitor = memory_guard.clone();
+	let final_stats = stats.clone();
+	let tracker = std::sync::Arc::new(connection::Tracker::new(metrics.get_ref().clone(), stats.clone(), trace_connections, h2_max_streams,
+		(overflow_strategy == connection::OverflowStrategy::AcceptThen503).then_some(connections)));
 
-	let mut server = HttpServer::new(move || App::new()
+	let app_metrics = metrics.clone();
+	let app = move || App::new()
 	.app_data(response_data.clone())
-	.configure(config_routes));
+	.app_data(web::PayloadConfig::new(max_body_size))
+	.app_data(web::JsonConfig::default().error_handler(json::extractor_error))
+	.app_data(MaxBodySize(max_body_size))
+	.app_data(redirect::MaxRedirects(max_redirects))
+	.app_data(matrix_limits)
+	.app_data(overflow_strategy)
+	.app_data(app_metrics.clone())
+	.app_data(stats.clone())
+	.app_data(stats::WorkerInFlight::register(&stats))
+	.app_data(watchdog.start(&stats))
+	.app_data(multi_status.clone())
+	.app_data(sequence.clone())
+	.app_data(broadcasts.clone())
+	.app_data(long_poll.clone())
+	.app_data(concurrent_writes.clone())
+	.app_data(post_redirect_get.clone())
+	.app_data(warmup.clone())
+	.app_data(batch.clone())
+	.app_data(listeners.clone())
+	.app_data(batch::MaxBatchSize(max_batch_size))
+	.configure(|cfg| if let Some(verifier) = &signature_verifier {
+		cfg.app_data(verifier.clone());
+	})
+	.app_data(endpoints.clone())
+	.app_data(idempotency.clone())
+	.configure(|cfg| if let Some(token) = &admin_token {
+		cfg.app_data(token.clone());
+	})
+	.configure(|cfg| if let Some(key) = &hmac_key {
+		cfg.app_data(key.clone());
+	})
+	.configure(|cfg| if let Some(content_types) = &content_types {
+		cfg.app_data(content_types.clone());
+	})
+	.configure(|cfg| if let Some(origins) = &allowed_origins {
+		cfg.app_data(origins.clone());
+	})
+	.configure(|cfg| if let Some(cache_control) = &cache_control {
+		cfg.app_data(cache_control.clone());
+	})
+	.configure(|cfg| if let Some(status_bodies) = &status_bodies {
+		cfg.app_data(status_bodies.clone());
+	})
+	.configure(|cfg| if let Some(template) = &body_template {
+		cfg.app_data(template.clone());
+	})
+	.configure(|cfg| if let Some(replay) = &sse_replay {
+		cfg.app_data(replay.clone());
+	})
+	.configure(|cfg| if let Some(guard) = &memory_guard {
+		cfg.app_data(guard.clone());
+	})
+	.configure(|cfg| if let Some(alerts) = &latency_alerts {
+		cfg.app_data(alerts.clone());
+	})
+	.configure(|cfg| if let Some(latency) = injected_latency {
+		cfg.app_data(latency);
+	})
+	.configure(|cfg| if let Some(limit) = request_header_limit {
+		cfg.app_data(limit);
+	})
+	.wrap(middleware::Condition::new(server_timing, middleware::from_fn(server_timing::handler)))
+	.wrap(middleware::from_fn(content_type::override_content_type))
+	.wrap(middleware::from_fn(cache_control::set_cache_control))
+	.wrap(middleware::from_fn(latency::inject))
+	.wrap(middleware::Condition::new(allow_method_override, middleware::from_fn(method_override::method_override)))
+	.wrap(middleware::Condition::new(strict_json, middleware::from_fn(json::strict_json)))
+	.wrap(middleware::from_fn(http_signature::verify))
+	.wrap(middleware::from_fn(headers::limit_request_headers))
+	.wrap(middleware::from_fn(admin::check_enabled))
+	.wrap(middleware::from_fn(admin::require_token))
+	.wrap(middleware::from_fn(error::problem_details))
+	.wrap(middleware::from_fn(cors::cors))
+	.wrap(middleware::from_fn(idempotency::deduplicate))
+	.wrap(middleware::from_fn(metrics::track))
+	.wrap(middleware::from_fn(latency::track))
+	.wrap(middleware::from_fn(connection::limit_streams))
+	.wrap(middleware::from_fn(connection::track_requests))
+	.wrap(middleware::Condition::new(overflow_strategy == connection::OverflowStrategy::AcceptThen503, middleware::from_fn(connection::reject_overflow)))
+	.wrap(middleware::Condition::new(memory_guard.is_some(), middleware::from_fn(memory::reject_when_degraded)))
+	.wrap(middleware::from_fn(stats::track_in_flight))
+	.wrap(middleware::Condition::new(trailing_slash == trailing_slash::TrailingSlash::Merge, middleware::NormalizePath::trim()))
+	.wrap(middleware::Condition::new(trailing_slash == trailing_slash::TrailingSlash::Redirect, middleware::from_fn(trailing_slash::redirect)))
+	.wrap(middleware::Condition::new(server_timing, middleware::from_fn(server_timing::server_timing)))
+	.configure(|cfg| if let Some(proxy) = &proxy {
+		proxy::Proxy::configure(cfg, proxy);
+	})
+	.configure(|cfg| if allow_disruption {
+		cfg.route("/reset-connection", web::get().to(disruption::reset_connection));
+		cfg.route("/simulate-timeout", web::get().to(disruption::simulate_timeout));
+	})
+	.configure(|cfg| config_routes(cfg, &disabled_routes))
+	.default_service(web::to(error::not_found));
+
+	let connect_tracker = tracker.clone();
+	batch_registry.register(app.clone());
+
+	let mut server = HttpServer::new(app.clone())
+	.disable_signals()
+	.on_connect(move |conn, ext| connect_tracker.on_connect(tls::connect_info(conn), ext));
 	
 	if workers > 0 	{
 		info!("set server workers to {}", workers);
 		server = server.workers(workers);
 	}
 
-	if connections > 0 	{
+	if accept_limit > 0 	{
 		info!("set server max connections to {}", connections);
-		server = server.max_connections(connections);
+		server = server.max_connections(accept_limit);
+	}
+
+	// process wide, the rustls server shares it
+	info!("set server max concurrent TLS handshakes per worker to {}", connection_rate);
+	server = server.max_connection_rate(connection_rate);
+
+	if matches.subcommand_matches("check").is_some() {
+		// getting here means the options and environment resolved
+		let mut checks = check::Checks::default();
+		checks.run("configuration", || Ok(format!("http on {}:{}, https port {}, {} workers", server_ip, http_port, https_port,
+			if workers > 0 { workers } else { num_cpus::get() })));
+		checks.run("http listener", || Ok(socket::bind(&format!("{}:{}", server_ip, http_port), &socket_options)?.local_addr()?.to_string()));
+		if https_port != 0 {
+			checks.run("https listener", || Ok(socket::bind(&format!("{}:{}", server_ip, https_port), &socket_options)?.local_addr()?.to_string()));
+		}
+		if tcp_echo_port != 0 {
+			checks.run("tcp echo listener", || Ok(socket::bind(&format!("{}:{}", server_ip, tcp_echo_port), &socket_options)?.local_addr()?.to_string()));
+		}
+		if https_port != 0 || http3_port != 0 {
+			checks.run("tls identity", || {
+				#[cfg(feature = "with_openssl")]
+				{
+					let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+					tls::load_openssl_identity(&mut builder, &cert_file_name, &key_file_name)?;
+					if let Some(ca_file_name) = &client_ca_file_name {
+						tls::load_openssl_client_ca(&mut builder, ca_file_name)?;
+					}
+				}
+				#[cfg(not(feature = "with_openssl"))]
+				tls::rustls_config(std::sync::Arc::new(tls::CertResolver::load(&cert_file_name, &key_file_name)?), client_ca_file_name.as_deref())?;
+				Ok(format!("{} matches {}", key_file_name, cert_file_name))
+			});
+			checks.run("certificate expiry", || check::certificate_validity(&cert_file_name));
+		}
+		#[cfg(feature = "http3")]
+		if http3_port != 0 {
+			checks.run("http3 listener", || Ok(http3::bind(format!("{}:{}", server_ip, http3_port).parse()?, &cert_file_name, &key_file_name)?.local_addr()?.to_string()));
+		}
+		return checks.finish();
+	}
+
+	if let Some(delay) = startup_delay {
+		// the ports stay unbound meanwhile, like a pod that isn't ready yet
+		info!("delaying startup by {:?} before binding", delay);
+		actix_web::rt::time::sleep(delay).await;
 	}
 
 	let http_address = format!("{}:{}", server_ip, http_port);
 
 	info!("http server listen on {}", http_address);
 	
-	let server = server.bind(http_address)?;
+	let server = server.listen(socket::bind(&http_address, &socket_options)?)?;
+
+	if let (Some(guard), Some(limit)) = (memory_monitor, memory_limit) {
+		info!("answering requests with 503 while resident memory is above {} bytes, checked every {:?}", limit, memory_check_interval);
+		actix_web::rt::spawn(memory::monitor(guard, memory_check_interval));
+	}
+
+	if tcp_echo_port != 0 {
+		let listener = tcp_echo::bind(&format!("{}:{}", server_ip, tcp_echo_port), &socket_options)?;
+		actix_web::rt::spawn(tcp_echo::serve(listener));
+	}
 
+	#[cfg(feature = "http3")]
+	if http3_port != 0 {
+		let http3_address = format!("{}:{}", server_ip, http3_port).parse()?;
+		let endpoint = http3::bind(http3_address, &cert_file_name, &key_file_name)?;
+		let app = app.clone();
+		actix_web::rt::spawn(async move {
+			if let Err(err) = http3::serve(endpoint, app).await {
+				error!("http3 server failed: {:#}", err);
+			}
+		});
+	}
+
+	// the shutdown hook runs however the servers stopped, their error is returned after it
+	let served: std::io::Result<()>;
 	if https_port != 0 {				   
 		let https_address = format!("{}:{}", server_ip, https_port);
 		info!("https server listen on {}", https_address);
+		if sni_policy.is_enabled() {
+			info!("https clients must send a server name{}", sni_policy.hostname.as_ref().map(|hostname| format!(" of {}", hostname)).unwrap_or_default());
+		}
 		#[cfg(feature = "with_openssl")]
 		{	
 			info!("https server build with openssl");	
 			let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-			builder.set_private_key_file(key_file_name, SslFiletype::PEM)?;
-			builder.set_certificate_chain_file(cert_file_name)?;
-			server.bind_openssl(https_address, builder)?.run().await?;
+			tls::load_openssl_identity(&mut builder, &cert_file_name, &key_file_name)?;
+			if let Some(ca_file_name) = &client_ca_file_name {
+				info!("https clients must present a certificate issued by {}", ca_file_name);
+				tls::load_openssl_client_ca(&mut builder, ca_file_name)?;
+			}
+			tls::time_openssl_handshakes(&mut builder);
+			if sni_policy.is_enabled() {
+				tls::check_openssl_sni(&mut builder, sni_policy);
+			}
+			let server = server.listen_openssl(socket::bind(&https_address, &socket_options)?, builder)?.run();
+			listener_registry.register(server.handle());
+			actix_web::rt::spawn(shutdown::stop_on_signal(shutdown_mode, vec![server.handle()]));
+			served = server.await;
 		}
 		#[cfg(not(feature = "with_openssl"))]
 		{
 			info!("https server build with rustls");	
-			let cert_file = &mut BufReader::new(File::open(cert_file_name)?);
-			let key_file = &mut BufReader::new(File::open(key_file_name)?);
-			
-			let cert_chain = certs(cert_file)?.into_iter().map(Certificate).collect();
-			let mut keys: Vec<PrivateKey> = pkcs8_private_keys(key_file)?.into_iter().map(PrivateKey).collect();
-			
-			if keys.is_empty() {
-				return Err(anyhow!("Could not locate PKCS 8 private keys."));
+			let resolver = std::sync::Arc::new(tls::CertResolver::load(&cert_file_name, &key_file_name)?.with_sni_policy(sni_policy));
+			if let Some(ca_file_name) = &client_ca_file_name {
+				info!("https clients must present a certificate issued by {}", ca_file_name);
 			}
-			let config = ServerConfig::builder().with_safe_defaults().with_no_client_auth();
-			server.bind_rustls(https_address, config.with_single_cert(cert_chain, keys.remove(0))?)?.run().await?;
+			let config = tls::rustls_config(resolver.clone(), client_ca_file_name.as_deref())?;
+			actix_web::rt::spawn(tls::watch_certs(resolver));
+			let https_server = tls::rustls_server(app, socket::bind(&https_address, &socket_options)?, config, move |info, ext| tracker.on_connect(info, ext), workers, accept_limit)?;
+			let server = server.run();
+			listener_registry.register(server.handle());
+			listener_registry.register(https_server.handle());
+			actix_web::rt::spawn(shutdown::stop_on_signal(shutdown_mode, vec![server.handle(), https_server.handle()]));
+			served = futures_util::future::try_join(server, https_server).await.map(|_| ());
 		}
 
 	} else {
-		server.run().await?;
+		let server = server.run();
+		listener_registry.register(server.handle());
+		actix_web::rt::spawn(shutdown::stop_on_signal(shutdown_mode, vec![server.handle()]));
+		served = server.await;
+	}
+
+	if let Some(command) = shutdown_hook {
+		shutdown::run_hook(&command, &final_stats);
 	}
+	served?;
 	
 	Ok(())
 
 }
 
-pub fn config_routes(cfg: &mut web::ServiceConfig) {
-	cfg
-	.route("/", web::get().to(index))
-	.route("/get", web::get().to(bench_get))
-	.route("/post", web::post().to(bench_post))
-	.route("/put", web::put().to(bench_put))
-	.route("/delete", web::delete().to(bench_delete));
+/// Registers every route whose path isn't in `disabled`, those fall through to the 404 handler.
+pub fn config_routes(cfg: &mut web::ServiceConfig, disabled: &HashSet<String>) {
+	for (path, route) in routes() {
+		if !disabled.contains(path) {
+			cfg.route(path, route);
+		}
+	}
+}
+
+fn routes() -> Vec<(&'static str, actix_web::Route)> {
+	vec![
+		("/metrics", web::get().to(metrics::metrics)),
+		("/stats", web::get().to(stats::stats)),
+		("/ws-bench", web::get().to(ws::ws_bench)),
+		("/stress-gc", web::get().to(stress::stress_gc)),
+		("/multi-status", web::to(status::multi_status)),
+		("/status/{code}", web::to(status::status)),
+		("/slow-headers", web::get().to(slow::slow_headers)),
+		("/slow-read", web::post().to(slow::slow_read)),
+		("/slowstart", web::get().to(slow::slowstart)),
+		("/slow-start", web::get().to(slow::slow_start)),
+		("/simulate-db", web::get().to(simulate::simulate_db)),
+		("/simulate-cache", web::get().to(simulate::simulate_cache)),
+		("/simulate-memory-pressure", web::get().to(simulate::simulate_memory_pressure)),
+		("/benchmark-matrix", web::get().to(matrix::benchmark_matrix)),
+		("/server-timing", web::get().to(server_timing::server_timing_endpoint)),
+		("/download", web::get().to(download::download)),
+		("/throughput", web::get().to(throughput::send)),
+		("/throughput", web::post().to(throughput::receive)),
+		("/redirect-chain-report", web::get().to(redirect::redirect_chain_report)),
+		("/sequence", web::get().to(sequence::sequence)),
+		("/random", web::get().to(random::random)),
+		("/text", web::get().to(lorem::text)),
+		("/set-cookies", web::get().to(cookies::set_cookies)),
+		("/large-headers", web::get().to(headers::large_headers)),
+		("/echo-query", web::get().to(echo::echo_query)),
+		("/mirror-header", web::get().to(echo::mirror_header)),
+		("/frame-echo", web::post().to(echo::frame_echo)),
+		("/batch", web::post().to(batch::batch)),
+		("/template", web::get().to(template::template)),
+		("/large-headers-request", web::to(headers::large_headers_request)),
+		("/echo-trailers", web::post().to(headers::echo_trailers)),
+		("/benchmark-info", web::get().to(info::benchmark_info)),
+		("/version", web::get().to(info::version)),
+		("/hash", web::post().to(hash::hash)),
+		("/chunked-upload", web::post().to(upload::chunked_upload)),
+		("/post-multipart-echo", web::post().to(upload::multipart_echo)),
+		("/reflect-timing", web::post().to(upload::reflect_timing)),
+		("/json-patch", web::patch().to(json::json_patch)),
+		("/validate-json", web::post().to(json::validate_json)),
+		("/jsonrpc", web::post().to(jsonrpc::jsonrpc)),
+		("/sign", web::post().to(signing::sign)),
+		("/verify-signature", web::post().to(signing::verify_signature)),
+		("/connection-id", web::get().to(connection::connection_id)),
+		("/connection-stats", web::get().to(connection::connection_stats)),
+		("/h2-streams", web::get().to(connection::h2_streams)),
+		("/proto", web::get().to(connection::proto)),
+		("/tls-info", web::get().to(connection::tls_info)),
+		("/mutual-tls-info", web::get().to(connection::mutual_tls_info)),
+		("/long-poll", web::get().to(longpoll::long_poll)),
+		("/continue-stream", web::get().to(infinite::continue_stream)),
+		("/publish", web::post().to(longpoll::publish)),
+		("/sse-broadcast", web::get().to(sse::subscribe)),
+		("/sse-broadcast", web::post().to(sse::publish)),
+		("/sse-clock", web::get().to(sse::clock)),
+		("/sse-replay", web::get().to(sse::replay)),
+		("/benchmark/concurrent-writes", web::get().to(writes::list)),
+		("/benchmark/concurrent-writes", web::post().to(writes::append)),
+		("/benchmark/fanout", web::get().to(fanout::fanout)),
+		("/prg", web::post().to(prg::post)),
+		("/prg/{id}", web::get().to(prg::get)),
+		("/admin/reset", web::post().to(admin::reset)),
+		("/admin/pause", web::post().to(admin::pause)),
+		("/admin/resume", web::post().to(admin::resume)),
+		("/admin/endpoints", web::get().to(admin::endpoints)),
+		("/admin/endpoints", web::post().to(admin::update_endpoints)),
+		("/", web::get().to(index)),
+		("/get", web::get().to(bench_get)),
+		("/post", web::post().to(bench_post)),
+		("/put", web::put().to(bench_put)),
+		("/delete", web::delete().to(bench_delete)),
+	]
 }
 
 pub async fn index(data: web::Data<ResponseData>) -> HttpResponse  {
@@ -242,26 +1322,28 @@ pub async fn index(data: web::Data<ResponseData>) -> HttpResponse  {
 	.body(data.index.clone())
 }
 
-pub async fn bench_get(data: web::Data<ResponseData>) -> HttpResponse  {
+pub async fn bench_get(data: web::Data<ResponseData>, query: web::Query<jitter::JitterQuery>) -> HttpResponse  {
 	HttpResponse::Ok()
 	.insert_header(header::ContentType(mime::APPLICATION_JSON))
-	.body(data.get.clone())
+	.body(query.apply(&data.get))
 }
 
-pub async fn bench_post(data: web::Data<ResponseData>) -> HttpResponse  {
-	HttpResponse::Ok()
+pub async fn bench_post(req: HttpRequest, body: web::Bytes, data: web::Data<ResponseData>, query: web::Query<jitter::JitterQuery>) -> Result<HttpResponse, error::BenchError>  {
+	json::check_declared_body(&req, &body)?;
+	std::result::Result::Ok(HttpResponse::Ok()
 	.insert_header(header::ContentType(mime::APPLICATION_JSON))
-	.body(data.post.clone())
+	.body(query.apply(&data.post)))
 }
 
-pub async fn bench_put(data: web::Data<ResponseData>) -> HttpResponse  {
-	HttpResponse::Ok()
+pub async fn bench_put(req: HttpRequest, body: web::Bytes, data: web::Data<ResponseData>, query: web::Query<jitter::JitterQuery>) -> Result<HttpResponse, error::BenchError>  {
+	json::check_declared_body(&req, &body)?;
+	std::result::Result::Ok(HttpResponse::Ok()
 	.insert_header(header::ContentType(mime::APPLICATION_JSON))
-	.body(data.put.clone())
+	.body(query.apply(&data.put)))
 }
 
-pub async fn bench_delete(data: web::Data<ResponseData>) -> HttpResponse  {
+pub async fn bench_delete(data: web::Data<ResponseData>, query: web::Query<jitter::JitterQuery>) -> HttpResponse  {
 	HttpResponse::Ok()
 	.insert_header(header::ContentType(mime::APPLICATION_JSON))
-	.body(data.delete.clone())
+	.body(query.apply(&data.delete))
 }
\ No newline at end of file
@@ -0,0 +1,59 @@
+use actix_web::{http::header, rt, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::error::BenchError;
+
+pub const DEFAULT_MAX_DELAY_MS: u64 = 60_000;
+pub const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+/// `{}`, the smallest JSON object.
+const MIN_SIZE: usize = 2;
+
+/// Upper bounds of `/benchmark-matrix`, `--matrix-max-delay` and `--matrix-max-size`.
+#[derive(Clone, Copy)]
+pub struct MatrixLimits {
+	pub max_delay_ms: u64,
+	pub max_size: usize,
+}
+
+#[derive(Deserialize)]
+pub struct MatrixQuery {
+	delay_ms: u64,
+	size_bytes: usize,
+}
+
+/// A JSON object of exactly `size` bytes describing the cell, padded with a string member,
+/// or with whitespace when even the description doesn't fit.
+fn cell_body(delay_ms: u64, size: usize) -> String {
+	let head = format!("{{\"delay_ms\":{},\"size_bytes\":{},\"padding\":\"", delay_ms, size);
+	let tail = "\"}";
+	match size.checked_sub(head.len() + tail.len()) {
+		Some(padding) => {
+			let mut body = String::with_capacity(size);
+			body.push_str(&head);
+			body.extend(std::iter::repeat_n('x', padding));
+			body.push_str(tail);
+			body
+		}
+		None => format!("{{{}}}", " ".repeat(size - MIN_SIZE)),
+	}
+}
+
+/// `/benchmark-matrix?delay_ms=<n>&size_bytes=<m>`: one cell of a latency × payload size
+/// matrix, a JSON body of exactly `size_bytes` sent after `delay_ms`.
+pub async fn benchmark_matrix(req: HttpRequest, query: web::Query<MatrixQuery>) -> Result<HttpResponse, BenchError> {
+	let limits = req.app_data::<MatrixLimits>().copied()
+		.unwrap_or(MatrixLimits{max_delay_ms: DEFAULT_MAX_DELAY_MS, max_size: DEFAULT_MAX_SIZE});
+	let MatrixQuery{delay_ms, size_bytes} = query.into_inner();
+	if delay_ms > limits.max_delay_ms {
+		return Err(BenchError::bad_request(format!("delay_ms must be at most {}", limits.max_delay_ms)));
+	}
+	if !(MIN_SIZE..=limits.max_size).contains(&size_bytes) {
+		return Err(BenchError::bad_request(format!("size_bytes must be between {} and {}", MIN_SIZE, limits.max_size)));
+	}
+
+	rt::time::sleep(Duration::from_millis(delay_ms)).await;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(cell_body(delay_ms, size_bytes)))
+}
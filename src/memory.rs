@@ -0,0 +1,105 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::{header, StatusCode},
+	middleware::Next,
+	rt, web, ResponseError,
+};
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::error::BenchError;
+
+pub const DEFAULT_CHECK_INTERVAL_MS: u64 = 1000;
+/// Degraded mode ends once RSS is back below this share of the limit, so a process hovering
+/// around the limit doesn't flap in and out of it.
+const RECOVERY_RATIO: f64 = 0.9;
+const RETRY_AFTER: u64 = 1;
+/// Still answered while degraded, to watch the server recover.
+const EXEMPT_PREFIXES: [&str; 3] = ["/stats", "/metrics", "/admin/"];
+
+/// `--memory-limit`: the resident set size above which new requests get a 503.
+pub struct MemoryGuard {
+	limit: u64,
+	rss: AtomicU64,
+	degraded: AtomicBool,
+	rejected: AtomicU64,
+}
+
+impl MemoryGuard {
+	pub fn new(limit: u64) -> Self {
+		MemoryGuard{limit, rss: AtomicU64::new(0), degraded: AtomicBool::new(false), rejected: AtomicU64::new(0)}
+	}
+
+	/// For `/stats`.
+	pub fn snapshot(&self) -> Value {
+		json!({
+			"limit_bytes": self.limit,
+			"rss_bytes": self.rss.load(Ordering::Relaxed),
+			"degraded": self.degraded.load(Ordering::Relaxed),
+			"rejected": self.rejected.load(Ordering::Relaxed),
+		})
+	}
+
+	fn update(&self, rss: u64) {
+		self.rss.store(rss, Ordering::Relaxed);
+		let degraded = self.degraded.load(Ordering::Relaxed);
+		if !degraded && rss > self.limit {
+			self.degraded.store(true, Ordering::Relaxed);
+			warn!("resident memory {} bytes is over the limit of {}, answering new requests with 503", rss, self.limit);
+		} else if degraded && (rss as f64) < self.limit as f64 * RECOVERY_RATIO {
+			self.degraded.store(false, Ordering::Relaxed);
+			info!("resident memory {} bytes is back under the limit of {}, serving requests again", rss, self.limit);
+		}
+	}
+}
+
+/// The process' resident set size from `/proc/self/statm`.
+#[cfg(target_os = "linux")]
+pub fn resident_bytes() -> std::io::Result<u64> {
+	let statm = std::fs::read_to_string("/proc/self/statm")?;
+	let pages = statm.split_whitespace().nth(1)
+		.and_then(|pages| pages.parse::<u64>().ok())
+		.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "unexpected /proc/self/statm format"))?;
+	// SAFETY: sysconf only reads a configuration value
+	let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+	Ok(pages * page_size.max(0) as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn resident_bytes() -> std::io::Result<u64> {
+	Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "reading the resident set size needs Linux"))
+}
+
+/// Checks the resident set size every `interval` for as long as the server runs.
+pub async fn monitor(guard: web::Data<MemoryGuard>, interval: Duration) {
+	let mut ticker = rt::time::interval(interval);
+	loop {
+		ticker.tick().await;
+		match resident_bytes() {
+			Ok(rss) => guard.update(rss),
+			Err(err) => warn!("could not read the resident memory size: {}", err),
+		}
+	}
+}
+
+/// Answers requests with 503 while `--memory-limit` is exceeded, `/stats`, `/metrics` and
+/// the admin routes excepted.
+pub async fn reject_when_degraded(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	let degraded = req.app_data::<web::Data<MemoryGuard>>()
+		.filter(|guard| guard.degraded.load(Ordering::Relaxed))
+		.filter(|_| !EXEMPT_PREFIXES.iter().any(|prefix| req.path().starts_with(prefix)));
+	let Some(guard) = degraded else {
+		return Ok(next.call(req).await?.map_into_boxed_body());
+	};
+	guard.rejected.fetch_add(1, Ordering::Relaxed);
+	let err = BenchError::new(StatusCode::SERVICE_UNAVAILABLE, format!("the server is over its memory limit of {} bytes, retry later", guard.limit));
+	let mut response = err.problem(Some(req.path().to_string())).error_response();
+	response.headers_mut().insert(header::RETRY_AFTER, header::HeaderValue::from(RETRY_AFTER));
+	Ok(req.into_response(response))
+}
@@ -0,0 +1,33 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::Method,
+	middleware::Next,
+};
+
+use crate::error::BenchError;
+
+const METHOD_OVERRIDE_HEADER: &str = "x-http-method-override";
+
+/// Dispatches `POST` requests carrying `X-HTTP-Method-Override` as the method named in the
+/// header. Only `PUT`, `DELETE` and `PATCH` may be tunneled, anything else is a 400.
+pub async fn method_override(
+	mut req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	if req.method() == Method::POST {
+		if let Some(value) = req.headers().get(METHOD_OVERRIDE_HEADER) {
+			let method = match value.to_str().map(str::trim) {
+				Ok(method) if method.eq_ignore_ascii_case("PUT") => Method::PUT,
+				Ok(method) if method.eq_ignore_ascii_case("DELETE") => Method::DELETE,
+				Ok(method) if method.eq_ignore_ascii_case("PATCH") => Method::PATCH,
+				_ => {
+					let err = BenchError::bad_request(format!("unsupported {} value, expected PUT, DELETE or PATCH", METHOD_OVERRIDE_HEADER));
+					return Ok(req.error_response(err));
+				}
+			};
+			req.head_mut().method = method;
+		}
+	}
+	Ok(next.call(req).await?.map_into_boxed_body())
+}
@@ -0,0 +1,118 @@
+use actix_web::{
+	body::MessageBody,
+	dev::{ServiceRequest, ServiceResponse},
+	http::header,
+	middleware::Next,
+	web, HttpResponse,
+};
+use hdrhistogram::Histogram;
+use prometheus::{Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// TLS handshake histogram buckets, 1ms to 500ms.
+const HANDSHAKE_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5];
+/// Handshake quantiles exported precisely, the buckets above only allow estimates.
+const HANDSHAKE_QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+/// Slowest recordable handshake in microseconds, slower ones count as this.
+const MAX_HANDSHAKE_US: u64 = 60_000_000;
+
+/// Prometheus metrics exposed at `/metrics`.
+#[derive(Clone)]
+pub struct Metrics {
+	registry: Registry,
+	requests: IntCounterVec,
+	request_duration: HistogramVec,
+	tls_handshake: HistogramVec,
+	tls_handshake_quantiles: GaugeVec,
+	/// every handshake since startup, behind `tls_handshake_quantiles`
+	handshakes: Arc<Mutex<Histogram<u64>>>,
+}
+
+impl Metrics {
+	pub fn new() -> Self {
+		let registry = Registry::new();
+
+		let requests = IntCounterVec::new(
+			Opts::new("http_requests_total", "Requests handled, by method, route and status"),
+			&["method", "route", "status"],
+		).unwrap();
+		let request_duration = HistogramVec::new(
+			HistogramOpts::new("http_request_duration_seconds", "Request handling time, by route"),
+			&["route"],
+		).unwrap();
+		let tls_handshake = HistogramVec::new(
+			HistogramOpts::new("tls_handshake_duration_seconds", "TLS handshake time, by protocol version")
+				.buckets(HANDSHAKE_BUCKETS.to_vec()),
+			&["version"],
+		).unwrap();
+
+		let tls_handshake_quantiles = GaugeVec::new(
+			Opts::new("tls_handshake_duration_quantile_seconds", "TLS handshake time quantiles over all versions since startup"),
+			&["quantile"],
+		).unwrap();
+
+		registry.register(Box::new(requests.clone())).unwrap();
+		registry.register(Box::new(request_duration.clone())).unwrap();
+		registry.register(Box::new(tls_handshake.clone())).unwrap();
+		registry.register(Box::new(tls_handshake_quantiles.clone())).unwrap();
+
+		let handshakes = Histogram::new_with_bounds(1, MAX_HANDSHAKE_US, 3).expect("valid histogram bounds");
+		Metrics{registry, requests, request_duration, tls_handshake, tls_handshake_quantiles, handshakes: Arc::new(Mutex::new(handshakes))}
+	}
+
+	pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+		self.requests.with_label_values(&[method, route, &status.to_string()]).inc();
+		self.request_duration.with_label_values(&[route]).observe(elapsed.as_secs_f64());
+	}
+
+	pub fn observe_handshake(&self, version: &str, elapsed: Duration) {
+		self.tls_handshake.with_label_values(&[version]).observe(elapsed.as_secs_f64());
+		self.handshakes.lock().unwrap().saturating_record(elapsed.as_micros().min(MAX_HANDSHAKE_US as u128) as u64);
+	}
+
+	fn render(&self) -> String {
+		let handshakes = self.handshakes.lock().unwrap();
+		if !handshakes.is_empty() {
+			for quantile in HANDSHAKE_QUANTILES {
+				let seconds = handshakes.value_at_quantile(quantile) as f64 / 1_000_000.0;
+				self.tls_handshake_quantiles.with_label_values(&[&quantile.to_string()]).set(seconds);
+			}
+		}
+		drop(handshakes);
+
+		let mut buffer = Vec::new();
+		// encoding into a Vec only fails on malformed metric families, which we never build
+		TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+		String::from_utf8(buffer).unwrap_or_default()
+	}
+}
+
+/// Diagnostic routes a client calls next to a benchmark, left out to keep its numbers clean.
+const UNTRACKED_ROUTES: [&str; 1] = ["/proto"];
+
+/// Middleware recording request count and latency per matched route.
+pub async fn track(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+	let start = Instant::now();
+	let res = next.call(req).await?;
+
+	if let Some(metrics) = metrics {
+		let request = res.request();
+		let route = request.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+		if UNTRACKED_ROUTES.contains(&route.as_str()) {
+			return Ok(res);
+		}
+		metrics.observe_request(request.method().as_str(), &route, res.status().as_u16(), start.elapsed());
+	}
+	Ok(res)
+}
+
+pub async fn metrics(metrics: web::Data<Metrics>) -> HttpResponse {
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(TextEncoder::new().format_type().parse().unwrap()))
+	.body(metrics.render())
+}
@@ -0,0 +1,57 @@
+use actix_web::{http::{header, StatusCode}, web, HttpResponse};
+use dashmap::DashMap;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::error::BenchError;
+
+/// How long a posted body can be fetched.
+const TTL: Duration = Duration::from_secs(60);
+/// A POST finding this many bodies stored sweeps the expired ones, and gets a 507 if they
+/// are still this many.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Bodies posted to `/prg`, waiting for the GET that follows the redirect. Expired entries
+/// go on their next GET, or in a sweep once the store is full.
+#[derive(Default)]
+pub struct PostRedirectGet {
+	entries: DashMap<Uuid, (web::Bytes, Instant)>,
+}
+
+/// `POST /prg`: stores the body under a fresh UUID and answers `303 See Other` pointing at
+/// `/prg/<uuid>`.
+pub async fn post(body: web::Bytes, prg: web::Data<PostRedirectGet>) -> Result<HttpResponse, BenchError> {
+	if prg.entries.len() >= MAX_ENTRIES {
+		prg.entries.retain(|_, (_, stored)| stored.elapsed() < TTL);
+		if prg.entries.len() >= MAX_ENTRIES {
+			return Err(BenchError::new(StatusCode::INSUFFICIENT_STORAGE, format!("{} bodies are already stored", MAX_ENTRIES)));
+		}
+	}
+	let id = Uuid::new_v4();
+	prg.entries.insert(id, (body, Instant::now()));
+	Ok(HttpResponse::SeeOther()
+	.insert_header((header::LOCATION, format!("/prg/{}", id)))
+	.finish())
+}
+
+/// `GET /prg/{id}`: the posted body, inlined as JSON when it is JSON and as a string
+/// otherwise. Gone after 60 seconds.
+pub async fn get(id: web::Path<Uuid>, prg: web::Data<PostRedirectGet>) -> Result<HttpResponse, BenchError> {
+	let id = id.into_inner();
+	let entry = prg.entries.get(&id).map(|entry| entry.value().clone());
+	let (body, stored) = match entry {
+		Some((body, stored)) if stored.elapsed() < TTL => (body, stored),
+		expired => {
+			if expired.is_some() {
+				prg.entries.remove(&id);
+			}
+			return Err(BenchError::new(StatusCode::NOT_FOUND, format!("no body stored under {}", id)));
+		}
+	};
+	let body = serde_json::from_slice::<serde_json::Value>(&body)
+		.unwrap_or_else(|_| String::from_utf8_lossy(&body).into());
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"id": id, "body": body, "age_ms": stored.elapsed().as_millis() as u64}).to_string()))
+}
@@ -0,0 +1,133 @@
+use actix_web::{http::StatusCode, rt, web, HttpRequest, HttpResponse};
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::error::BenchError;
+use crate::stats::Stats;
+
+pub const DEFAULT_PREFIX: &str = "/proxy-pass";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Request body chunks buffered between the client and the upstream.
+const BODY_BUFFER_CHUNKS: usize = 8;
+
+/// Headers that only apply to a single hop and are never forwarded (RFC 9110 section 7.6.1).
+const HOP_BY_HOP: &[&str] = &[
+	"connection", "keep-alive", "proxy-authenticate", "proxy-authorization",
+	"proxy-connection", "te", "trailer", "transfer-encoding", "upgrade",
+];
+
+/// `--upstream`: requests under `prefix` are forwarded to `upstream`, the rest of the path
+/// appended to the upstream url.
+pub struct Proxy {
+	upstream: String,
+	prefix: String,
+	client: reqwest::Client,
+}
+
+impl Proxy {
+	pub fn new(upstream: &str, prefix: &str) -> Result<Self> {
+		let url = reqwest::Url::parse(upstream).with_context(|| format!("invalid upstream url {}", upstream))?;
+		let client = reqwest::Client::builder()
+			.connect_timeout(CONNECT_TIMEOUT)
+			.redirect(reqwest::redirect::Policy::none())
+			.build()?;
+		Ok(Proxy{
+			upstream: url.as_str().trim_end_matches('/').to_string(),
+			prefix: prefix.trim_end_matches('/').to_string(),
+			client,
+		})
+	}
+
+	pub fn configure(cfg: &mut web::ServiceConfig, proxy: &web::Data<Proxy>) {
+		cfg.app_data(proxy.clone())
+		.route(&format!("{}{{tail:(/.*)?}}", proxy.prefix), web::to(forward));
+	}
+}
+
+/// Hop-by-hop headers plus any the `Connection` header lists.
+fn is_hop_by_hop(name: &str, connection: &[String]) -> bool {
+	HOP_BY_HOP.contains(&name) || connection.iter().any(|listed| listed.eq_ignore_ascii_case(name))
+}
+
+fn connection_tokens<'a>(values: impl Iterator<Item = &'a [u8]>) -> Vec<String> {
+	values.filter_map(|value| std::str::from_utf8(value).ok())
+		.flat_map(|value| value.split(','))
+		.map(|token| token.trim().to_string())
+		.collect()
+}
+
+fn bad_gateway(err: impl std::fmt::Display) -> BenchError {
+	BenchError::new(StatusCode::BAD_GATEWAY, format!("upstream request failed: {}", err))
+}
+
+/// Relays the request to the upstream and streams its response back, both bodies are passed
+/// through chunk by chunk rather than buffered.
+pub async fn forward(
+	req: HttpRequest,
+	mut payload: web::Payload,
+	proxy: web::Data<Proxy>,
+	stats: web::Data<Stats>,
+) -> Result<HttpResponse, BenchError> {
+	let tail = req.match_info().query("tail");
+	let url = match req.uri().query() {
+		Some(query) => format!("{}{}?{}", proxy.upstream, tail, query),
+		None => format!("{}{}", proxy.upstream, tail),
+	};
+	let method = reqwest::Method::from_bytes(req.method().as_str().as_bytes()).map_err(bad_gateway)?;
+
+	let connection = connection_tokens(req.headers().get_all("connection").map(|value| value.as_bytes()));
+	let mut upstream_req = proxy.client.request(method, url);
+	for (name, value) in req.headers() {
+		if name == "host" || is_hop_by_hop(name.as_str(), &connection) {
+			continue;
+		}
+		upstream_req = upstream_req.header(name.as_str(), value.as_bytes());
+	}
+
+	// HTTP/2 bodies need neither content-length nor transfer-encoding, whether there is one
+	// only shows once the first bytes arrive or the stream ends
+	let mut first = None;
+	while let Some(chunk) = payload.next().await {
+		let chunk = chunk.map_err(|err| BenchError::bad_request(format!("request body failed: {}", err)))?;
+		if !chunk.is_empty() {
+			first = Some(chunk);
+			break;
+		}
+	}
+	if let Some(first) = first {
+		// the payload is tied to this worker thread, the upstream body has to be Send
+		let (tx, rx) = mpsc::channel::<io::Result<web::Bytes>>(BODY_BUFFER_CHUNKS);
+		rt::spawn(async move {
+			if tx.send(Ok(first)).await.is_err() {
+				return;
+			}
+			while let Some(chunk) = payload.next().await {
+				let chunk = chunk.map_err(|err| io::Error::other(err.to_string()));
+				if tx.send(chunk).await.is_err() {
+					break;
+				}
+			}
+		});
+		let body = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (chunk, rx)) });
+		upstream_req = upstream_req.body(reqwest::Body::wrap_stream(body));
+	}
+
+	let start = Instant::now();
+	let result = upstream_req.send().await;
+	stats.upstream.record(start.elapsed(), result.is_ok());
+	let upstream_res = result.map_err(bad_gateway)?;
+
+	let status = StatusCode::from_u16(upstream_res.status().as_u16()).map_err(bad_gateway)?;
+	let mut response = HttpResponse::build(status);
+	let connection = connection_tokens(upstream_res.headers().get_all("connection").iter().map(|value| value.as_bytes()));
+	for (name, value) in upstream_res.headers() {
+		if !is_hop_by_hop(name.as_str(), &connection) {
+			response.append_header((name.as_str(), value.as_bytes()));
+		}
+	}
+	Ok(response.streaming(upstream_res.bytes_stream().map(|chunk| chunk.map_err(bad_gateway))))
+}
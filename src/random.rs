@@ -0,0 +1,50 @@
+use actix_web::{http::header, web, HttpResponse};
+use base64::Engine;
+use rand::{rngs::OsRng, RngCore};
+use serde::Deserialize;
+
+use crate::error::BenchError;
+
+const DEFAULT_SIZE: usize = 32;
+const MAX_SIZE: usize = 1024 * 1024;
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+	#[default]
+	Raw,
+	Hex,
+	Base64,
+}
+
+#[derive(Deserialize)]
+pub struct RandomQuery {
+	size: Option<usize>,
+	#[serde(default)]
+	format: Format,
+}
+
+/// `/random?size=<bytes>&format=raw|hex|base64`: `size` bytes straight from the OS entropy
+/// source.
+pub async fn random(query: web::Query<RandomQuery>) -> Result<HttpResponse, BenchError> {
+	let size = query.size.unwrap_or(DEFAULT_SIZE);
+	if size > MAX_SIZE {
+		return Err(BenchError::bad_request(format!("size must be at most {} bytes", MAX_SIZE)));
+	}
+
+	let mut bytes = vec![0u8; size];
+	OsRng.fill_bytes(&mut bytes);
+
+	let response = match query.format {
+		Format::Raw => HttpResponse::Ok()
+			.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+			.body(bytes),
+		Format::Hex => HttpResponse::Ok()
+			.insert_header(header::ContentType(mime::TEXT_PLAIN))
+			.body(hex::encode(bytes)),
+		Format::Base64 => HttpResponse::Ok()
+			.insert_header(header::ContentType(mime::TEXT_PLAIN))
+			.body(base64::engine::general_purpose::STANDARD.encode(bytes)),
+	};
+	Ok(response)
+}
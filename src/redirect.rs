@@ -0,0 +1,70 @@
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::BenchError;
+
+pub const DEFAULT_MAX_REDIRECTS: usize = 20;
+const REDIRECT_HOP_HEADER: &str = "x-redirect-hop";
+
+/// Longest redirect chain a client may ask for, `--max-redirects`.
+#[derive(Clone, Copy)]
+pub struct MaxRedirects(pub usize);
+
+#[derive(Deserialize)]
+pub struct RedirectChainQuery {
+	hops: usize,
+	/// arrival times of the requests so far in unix microseconds, carried along the chain
+	#[serde(default)]
+	trail: String,
+}
+
+fn unix_micros() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_micros() as u64)
+}
+
+/// `/redirect-chain-report?hops=<n>`: answers `n` times with a 302 back to itself, tagged
+/// `X-Redirect-Hop: 1` to `n`, then with a JSON report of every hop and the time between
+/// them. The chain is tracked in the `trail` query parameter the redirects add.
+pub async fn redirect_chain_report(req: HttpRequest, query: web::Query<RedirectChainQuery>) -> Result<HttpResponse, BenchError> {
+	let max_redirects = req.app_data::<MaxRedirects>().map_or(DEFAULT_MAX_REDIRECTS, |max| max.0);
+	if query.hops > max_redirects {
+		return Err(BenchError::bad_request(format!("hops must be at most {}", max_redirects)));
+	}
+	let mut trail = query.trail.split(',')
+		.filter(|at| !at.is_empty())
+		.map(str::parse::<u64>)
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| BenchError::bad_request("trail must be a comma separated list of timestamps"))?;
+	if trail.len() > query.hops {
+		return Err(BenchError::bad_request("trail is longer than the chain"));
+	}
+	trail.push(unix_micros());
+
+	let hop = trail.len();
+	if hop <= query.hops {
+		let trail = trail.iter().map(u64::to_string).collect::<Vec<_>>().join(",");
+		let location = format!("{}?hops={}&trail={}", req.path(), query.hops, trail);
+		return Ok(HttpResponse::Found()
+			.insert_header((header::LOCATION, location))
+			.insert_header((REDIRECT_HOP_HEADER, hop.to_string()))
+			.finish());
+	}
+
+	let hops: Vec<_> = trail.windows(2).enumerate()
+		.map(|(index, pair)| json!({
+			"hop": index + 1,
+			"at_us": pair[1],
+			"latency_us": pair[1].saturating_sub(pair[0]),
+		}))
+		.collect();
+	let body = json!({
+		"hops": query.hops,
+		"traversed": hops,
+		"total_us": trail[trail.len() - 1].saturating_sub(trail[0]),
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
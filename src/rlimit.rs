@@ -0,0 +1,35 @@
+use log::*;
+
+/// Logs the open file limit and warns when it can't hold `connections`, each of which may
+/// need a second descriptor for an upstream or file. With `raise` the soft limit is lifted
+/// to the hard limit first.
+#[cfg(unix)]
+pub fn check_nofile(connections: usize, raise: bool) {
+	// SAFETY: getrlimit/setrlimit only read and write the rlimit struct passed in
+	let mut limit = libc::rlimit{rlim_cur: 0, rlim_max: 0};
+	if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+		warn!("could not read the open file limit: {}", std::io::Error::last_os_error());
+		return;
+	}
+
+	if raise && limit.rlim_cur < limit.rlim_max {
+		let raised = libc::rlimit{rlim_cur: limit.rlim_max, rlim_max: limit.rlim_max};
+		if unsafe { libc::setrlimit(libc::RLIMIT_NOFILE, &raised) } == 0 {
+			info!("raised the open file soft limit from {} to {}", limit.rlim_cur, raised.rlim_cur);
+			limit = raised;
+		} else {
+			warn!("could not raise the open file soft limit to {}: {}", limit.rlim_max, std::io::Error::last_os_error());
+		}
+	}
+
+	info!("open file limit: soft {}, hard {}", limit.rlim_cur, limit.rlim_max);
+	let needed = (connections as libc::rlim_t).saturating_mul(2);
+	if limit.rlim_cur != libc::RLIM_INFINITY && needed > limit.rlim_cur {
+		let hint = if raise { "raise the hard limit" } else { "raise it with ulimit -n or --raise-nofile" };
+		warn!("max connections {} may need {} file descriptors but the soft limit is {}, {}",
+			connections, needed, limit.rlim_cur, hint);
+	}
+}
+
+#[cfg(not(unix))]
+pub fn check_nofile(_connections: usize, _raise: bool) {}
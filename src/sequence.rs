@@ -0,0 +1,43 @@
+use actix_web::{http::header, web, HttpResponse};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::BenchError;
+
+const MAX_SIZE: usize = 1024 * 1024;
+
+/// Process wide counter behind `/sequence`, shared by all workers and connections.
+#[derive(Default)]
+pub struct Sequence {
+	next: AtomicU64,
+}
+
+impl Sequence {
+	pub fn reset(&self) {
+		self.next.store(0, Ordering::Relaxed);
+	}
+}
+
+#[derive(Deserialize)]
+pub struct SequenceQuery {
+	size: Option<usize>,
+}
+
+/// `/sequence?size=<bytes>`: returns the next sequence number, optionally padded so the
+/// body is `size` bytes long. Gaps, duplicates or reordering show up on the client side.
+pub async fn sequence(query: web::Query<SequenceQuery>, state: web::Data<Sequence>) -> Result<HttpResponse, BenchError> {
+	let size = query.size.unwrap_or(0);
+	if size > MAX_SIZE {
+		return Err(BenchError::bad_request(format!("size must be at most {} bytes", MAX_SIZE)));
+	}
+
+	let number = state.next.fetch_add(1, Ordering::Relaxed);
+	let unpadded = format!("{{\"sequence\":{},\"padding\":\"\"}}", number).len();
+	let padding = "x".repeat(size.saturating_sub(unpadded));
+	let body = format!("{{\"sequence\":{},\"padding\":\"{}\"}}", number, padding);
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.insert_header(("X-Sequence", number.to_string()))
+	.body(body))
+}
@@ -0,0 +1,82 @@
+use actix_web::{
+	body::MessageBody,
+	dev::{ServiceRequest, ServiceResponse},
+	http::header::{self, HeaderName, HeaderValue},
+	middleware::Next,
+	rt, web, HttpMessage, HttpResponse,
+};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+use crate::error::BenchError;
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+const MAX_WORK_MS: u64 = 60_000;
+
+/// When the handler of a request started and returned, set by [`handler`].
+#[derive(Clone, Copy)]
+struct HandlerTiming {
+	start: Instant,
+	end: Option<Instant>,
+}
+
+fn millis(duration: Duration) -> String {
+	format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+/// `--server-timing`, the outermost middleware: adds `Server-Timing: recv;dur=N,
+/// handler;dur=N, serialize;dur=N` to every response, in milliseconds. `recv` runs from
+/// the request entering the app to the handler, through the other middlewares, `handler` is
+/// the handler itself and `serialize` the way back out until the response head is ready.
+/// Streamed bodies are still being produced after that.
+pub async fn server_timing(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let received = Instant::now();
+	let mut res = next.call(req).await?;
+	let done = Instant::now();
+
+	let handler = res.request().extensions().get::<HandlerTiming>().copied();
+	let value = match handler {
+		Some(HandlerTiming{start, end: Some(end)}) => format!("recv;dur={}, handler;dur={}, serialize;dur={}",
+			millis(start - received), millis(end - start), millis(done - end)),
+		// answered by a middleware, the handler never ran
+		_ => format!("recv;dur={}", millis(done - received)),
+	};
+	if let Ok(value) = HeaderValue::from_str(&value) {
+		res.headers_mut().append(SERVER_TIMING, value);
+	}
+	Ok(res)
+}
+
+/// `--server-timing`, the innermost middleware: times the handler for [`server_timing`].
+pub async fn handler(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	req.extensions_mut().insert(HandlerTiming{start: Instant::now(), end: None});
+	let res = next.call(req).await?;
+	if let Some(timing) = res.request().extensions_mut().get_mut::<HandlerTiming>() {
+		timing.end = Some(Instant::now());
+	}
+	Ok(res)
+}
+
+#[derive(Deserialize)]
+pub struct ServerTimingQuery {
+	#[serde(default)]
+	work_ms: u64,
+}
+
+/// `/server-timing?work_ms=<n>`: a handler taking `work_ms`, to see the phases of
+/// `--server-timing` with a known handler duration.
+pub async fn server_timing_endpoint(query: web::Query<ServerTimingQuery>) -> Result<HttpResponse, BenchError> {
+	if query.work_ms > MAX_WORK_MS {
+		return Err(BenchError::bad_request(format!("work_ms must be at most {}", MAX_WORK_MS)));
+	}
+	rt::time::sleep(Duration::from_millis(query.work_ms)).await;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::TEXT_PLAIN))
+	.body(format!("worked {}ms\n", query.work_ms)))
+}
@@ -0,0 +1,106 @@
+use actix_web::dev::ServerHandle;
+use anyhow::{anyhow, Result};
+use log::*;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+use crate::stats::Stats;
+
+/// What a shutdown signal does with the connections still open, `--shutdown-mode`.
+#[derive(Clone, Copy, Default)]
+pub enum ShutdownMode {
+	/// stop accepting and let in-flight requests finish, up to actix's shutdown timeout
+	#[default]
+	Graceful,
+	/// stop the workers right away, dropping every connection
+	Immediate,
+}
+
+impl FromStr for ShutdownMode {
+	type Err = anyhow::Error;
+
+	fn from_str(mode: &str) -> Result<Self> {
+		match mode {
+			"graceful" => Ok(ShutdownMode::Graceful),
+			"immediate" => Ok(ShutdownMode::Immediate),
+			_ => Err(anyhow!("unknown shutdown mode {}, expected graceful or immediate", mode)),
+		}
+	}
+}
+
+#[cfg(unix)]
+async fn signal_received() -> &'static str {
+	use tokio::signal::unix::{signal, SignalKind};
+	let (mut interrupt, mut terminate, mut quit) = match (signal(SignalKind::interrupt()), signal(SignalKind::terminate()), signal(SignalKind::quit())) {
+		(Ok(interrupt), Ok(terminate), Ok(quit)) => (interrupt, terminate, quit),
+		_ => {
+			error!("can't listen for shutdown signals");
+			return std::future::pending().await;
+		}
+	};
+	tokio::select! {
+		_ = interrupt.recv() => "SIGINT",
+		_ = terminate.recv() => "SIGTERM",
+		_ = quit.recv() => "SIGQUIT",
+	}
+}
+
+#[cfg(not(unix))]
+async fn signal_received() -> &'static str {
+	if tokio::signal::ctrl_c().await.is_err() {
+		error!("can't listen for shutdown signals");
+		std::future::pending::<()>().await;
+	}
+	"Ctrl-C"
+}
+
+/// Replaces actix's own signal handling, so every shutdown signal stops all `servers` the
+/// same way.
+pub async fn stop_on_signal(mode: ShutdownMode, servers: Vec<ServerHandle>) {
+	let signal = signal_received().await;
+	let graceful = match mode {
+		ShutdownMode::Graceful => {
+			info!("{} received, graceful shutdown: draining in-flight requests", signal);
+			true
+		}
+		ShutdownMode::Immediate => {
+			info!("{} received, immediate shutdown: dropping all connections", signal);
+			false
+		}
+	};
+	futures_util::future::join_all(servers.iter().map(|server| server.stop(graceful))).await;
+}
+
+/// Runs the `--shutdown-hook` command through the shell once the server stopped, with the
+/// final `/stats` JSON on its stdin.
+pub fn run_hook(command: &str, stats: &Stats) {
+	info!("running shutdown hook: {}", command);
+	#[cfg(unix)]
+	let mut shell = Command::new("sh");
+	#[cfg(unix)]
+	shell.arg("-c");
+	#[cfg(windows)]
+	let mut shell = Command::new("cmd");
+	#[cfg(windows)]
+	shell.arg("/C");
+
+	let mut child = match shell.arg(command).stdin(Stdio::piped()).spawn() {
+		Ok(child) => child,
+		Err(err) => {
+			error!("failed to start shutdown hook: {}", err);
+			return;
+		}
+	};
+	if let Some(mut stdin) = child.stdin.take() {
+		// the hook may well ignore its input and exit early, that's not an error
+		if let Err(err) = stdin.write_all(stats.snapshot().to_string().as_bytes()) {
+			debug!("shutdown hook did not read the stats: {}", err);
+		}
+	}
+	match child.wait() {
+		Ok(status) if status.success() => info!("shutdown hook finished"),
+		Ok(status) => warn!("shutdown hook exited with {}", status),
+		Err(err) => error!("failed to wait for shutdown hook: {}", err),
+	}
+}
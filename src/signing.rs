@@ -0,0 +1,70 @@
+use actix_web::{http::{header, StatusCode}, web, HttpRequest, HttpResponse};
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::error::BenchError;
+
+/// Header `/verify-signature` reads the hex signature from, `sha256=` prefix optional.
+const SIGNATURE_HEADER: &str = "x-signature";
+
+/// HMAC-SHA256 key of `/sign` and `/verify-signature`, `--hmac-key`.
+pub struct HmacKey {
+	key: Vec<u8>,
+	/// first 8 bytes of the key's SHA-256 in hex, names the key without revealing it
+	id: String,
+}
+
+impl HmacKey {
+	pub fn parse(hex_key: &str) -> Result<Self> {
+		let key = hex::decode(hex_key.trim()).map_err(|err| anyhow!("HMAC key is not hex: {}", err))?;
+		if key.is_empty() {
+			return Err(anyhow!("HMAC key is empty"));
+		}
+		let id = hex::encode(&Sha256::digest(&key)[..8]);
+		Ok(HmacKey{key, id})
+	}
+
+	pub fn id(&self) -> &str {
+		&self.id
+	}
+
+	fn mac(&self, body: &[u8]) -> Hmac<Sha256> {
+		let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+		mac.update(body);
+		mac
+	}
+}
+
+fn key(req: &HttpRequest) -> Result<&HmacKey, BenchError> {
+	req.app_data::<web::Data<HmacKey>>().map(|key| key.get_ref())
+		.ok_or_else(|| BenchError::new(StatusCode::NOT_IMPLEMENTED, "no HMAC key configured, start the server with --hmac-key"))
+}
+
+/// `POST /sign`: the HMAC-SHA256 of the body under the `--hmac-key` key, in hex.
+pub async fn sign(req: HttpRequest, body: web::Bytes) -> Result<HttpResponse, BenchError> {
+	let key = key(&req)?;
+	let signature = hex::encode(key.mac(&body).finalize().into_bytes());
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"signature": signature, "key_id": key.id}).to_string()))
+}
+
+/// `POST /verify-signature`: 200 when `X-Signature` holds the HMAC-SHA256 of the body under
+/// the `--hmac-key` key, 401 otherwise. The comparison is constant time.
+pub async fn verify_signature(req: HttpRequest, body: web::Bytes) -> Result<HttpResponse, BenchError> {
+	let key = key(&req)?;
+	let signature = req.headers().get(SIGNATURE_HEADER)
+		.and_then(|value| value.to_str().ok())
+		.map(|value| value.trim())
+		.map(|value| value.strip_prefix("sha256=").unwrap_or(value))
+		.ok_or_else(|| BenchError::new(StatusCode::UNAUTHORIZED, "missing X-Signature header"))?;
+	let signature = hex::decode(signature)
+		.map_err(|_| BenchError::new(StatusCode::UNAUTHORIZED, "X-Signature is not hex"))?;
+	key.mac(&body).verify_slice(&signature)
+		.map_err(|_| BenchError::new(StatusCode::UNAUTHORIZED, "signature does not match the body"))?;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"valid": true, "key_id": key.id}).to_string()))
+}
@@ -0,0 +1,137 @@
+use actix_web::{http::{header, StatusCode}, rt, web, HttpResponse};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use crate::error::BenchError;
+
+const MAX_LATENCY_MS: u64 = 60_000;
+const DEFAULT_ROWS: usize = 10;
+const DEFAULT_ROW_SIZE: usize = 64;
+const DEFAULT_HIT_RATE: f64 = 0.8;
+const DEFAULT_HIT_LATENCY_MS: u64 = 1;
+const DEFAULT_MISS_LATENCY_MS: u64 = 50;
+/// Upper bound of the serialized result, `rows * (row_size + ROW_OVERHEAD)`.
+const MAX_RESULT_BYTES: usize = 64 * 1024 * 1024;
+const MAX_ROWS: usize = 1_000_000;
+/// Bytes a row takes in the JSON besides its data, `{"id":<20 digits>,"data":""},`.
+const ROW_OVERHEAD: usize = 38;
+const MAX_PRESSURE_MB: usize = 512;
+
+#[derive(Deserialize)]
+pub struct SimulateDbQuery {
+	#[serde(default)]
+	query_ms: u64,
+	rows: Option<usize>,
+	row_size: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Row<'a> {
+	id: usize,
+	data: &'a str,
+}
+
+/// `/simulate-db?query_ms=<n>&rows=<r>&row_size=<s>`: waits `query_ms` like a database
+/// roundtrip, then serializes `r` rows whose `data` field holds `s` bytes as a JSON array.
+pub async fn simulate_db(query: web::Query<SimulateDbQuery>) -> Result<HttpResponse, BenchError> {
+	let rows = query.rows.unwrap_or(DEFAULT_ROWS);
+	let row_size = query.row_size.unwrap_or(DEFAULT_ROW_SIZE);
+	if query.query_ms > MAX_LATENCY_MS {
+		return Err(BenchError::bad_request(format!("query_ms must be at most {}", MAX_LATENCY_MS)));
+	}
+	if rows > MAX_ROWS {
+		return Err(BenchError::bad_request(format!("rows must be at most {}", MAX_ROWS)));
+	}
+	if rows.checked_mul(row_size.saturating_add(ROW_OVERHEAD)).is_none_or(|total| total > MAX_RESULT_BYTES) {
+		return Err(BenchError::bad_request(format!("rows * (row_size + {}) must not exceed {}", ROW_OVERHEAD, MAX_RESULT_BYTES)));
+	}
+
+	rt::time::sleep(Duration::from_millis(query.query_ms)).await;
+
+	// the rows are serialized one by one like a result set would be, sharing their payload
+	let data = "x".repeat(row_size);
+	let result: Vec<Row> = (1..=rows).map(|id| Row{id, data: &data}).collect();
+	let body = serde_json::to_string(&result).map_err(|err| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body))
+}
+
+#[derive(Deserialize)]
+pub struct SimulateCacheQuery {
+	hit_rate: Option<f64>,
+	hit_latency_ms: Option<u64>,
+	miss_latency_ms: Option<u64>,
+}
+
+/// `/simulate-cache?hit_rate=<0.0-1.0>&hit_latency_ms=<n>&miss_latency_ms=<n>`: a handler in
+/// front of a cache, each request hits with probability `hit_rate` and takes the hit or miss
+/// latency accordingly. The outcome is in the body and in `X-Cache-Status`.
+pub async fn simulate_cache(query: web::Query<SimulateCacheQuery>) -> Result<HttpResponse, BenchError> {
+	let hit_rate = query.hit_rate.unwrap_or(DEFAULT_HIT_RATE);
+	let hit_latency = query.hit_latency_ms.unwrap_or(DEFAULT_HIT_LATENCY_MS);
+	let miss_latency = query.miss_latency_ms.unwrap_or(DEFAULT_MISS_LATENCY_MS);
+	if !(0.0..=1.0).contains(&hit_rate) {
+		return Err(BenchError::bad_request("hit_rate must be between 0.0 and 1.0"));
+	}
+	if hit_latency.max(miss_latency) > MAX_LATENCY_MS {
+		return Err(BenchError::bad_request(format!("latencies must be at most {}ms", MAX_LATENCY_MS)));
+	}
+
+	let (hit, value) = {
+		let mut rng = rand::thread_rng();
+		(rng.gen_bool(hit_rate), format!("{:016x}", rng.gen::<u64>()))
+	};
+	let (status, latency) = if hit { ("hit", hit_latency) } else { ("miss", miss_latency) };
+	rt::time::sleep(Duration::from_millis(latency)).await;
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.insert_header(("X-Cache-Status", status.to_uppercase()))
+	.body(json!({"cache": status, "latency_ms": latency, "value": value}).to_string()))
+}
+
+#[derive(Deserialize)]
+pub struct MemoryPressureQuery {
+	mb: usize,
+	#[serde(default)]
+	hold_ms: u64,
+}
+
+/// `/simulate-memory-pressure?mb=<n>&hold_ms=<t>`: allocates `n` MiB, writing every page so
+/// it is really backed by memory, holds it for `t` ms and frees it again. Allocating and
+/// freeing run off the worker thread, concurrent requests only feel the pressure.
+pub async fn simulate_memory_pressure(query: web::Query<MemoryPressureQuery>) -> Result<HttpResponse, actix_web::Error> {
+	let MemoryPressureQuery{mb, hold_ms} = query.into_inner();
+	if mb > MAX_PRESSURE_MB {
+		return Err(BenchError::bad_request(format!("mb must be at most {}", MAX_PRESSURE_MB)).into());
+	}
+	if hold_ms > MAX_LATENCY_MS {
+		return Err(BenchError::bad_request(format!("hold_ms must be at most {}", MAX_LATENCY_MS)).into());
+	}
+
+	let (memory, alloc_time) = web::block(move || {
+		let start = Instant::now();
+		// not zeroes, those could come from untouched pages
+		let memory = std::hint::black_box(vec![0xa5u8; mb * 1024 * 1024]);
+		(memory, start.elapsed())
+	}).await?;
+	rt::time::sleep(Duration::from_millis(hold_ms)).await;
+	let free_time = web::block(move || {
+		let start = Instant::now();
+		drop(memory);
+		start.elapsed()
+	}).await?;
+
+	let body = json!({
+		"allocated_mb": mb,
+		"held_ms": hold_ms,
+		"alloc_time_us": alloc_time.as_micros() as u64,
+		"free_time_us": free_time.as_micros() as u64,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
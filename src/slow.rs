@@ -0,0 +1,228 @@
+use actix_web::{body::{BodySize, MessageBody}, http::{header, StatusCode}, rt, web, HttpRequest, HttpResponse};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::error::BenchError;
+use crate::MaxBodySize;
+
+const MAX_DELAY_MS: u64 = 60_000;
+const MAX_RATE: u64 = 1024 * 1024 * 1024;
+const MAX_RAMP_MS: u64 = 600_000;
+const MAX_SLOWSTART_SIZE: u64 = 1024 * 1024 * 1024;
+const DEFAULT_SLOWSTART_SIZE: u64 = 1024 * 1024;
+const DEFAULT_WARMUP_REQUESTS: u64 = 1000;
+const DEFAULT_WARMUP_DELAY_MS: u64 = 500;
+/// How often `/slowstart` sends what the rate allows so far.
+const SLOWSTART_TICK: Duration = Duration::from_millis(20);
+const SLOWSTART_CHUNK: usize = 64 * 1024;
+
+/// Every chunk of a `/slowstart` body is a slice of this, however much the rate allows.
+static SLOWSTART_FILL: [u8; SLOWSTART_CHUNK] = [b'x'; SLOWSTART_CHUNK];
+
+#[derive(Deserialize)]
+pub struct SlowHeadersQuery {
+	header_delay_ms: Option<u64>,
+	body_delay_ms: Option<u64>,
+}
+
+/// Body that stays pending for `delay` after the headers went out, then yields its bytes in
+/// a single chunk.
+pub struct DelayedBody {
+	delay: Pin<Box<rt::time::Sleep>>,
+	bytes: Option<web::Bytes>,
+	size: u64,
+}
+
+impl DelayedBody {
+	pub fn new(delay: Duration, bytes: web::Bytes) -> Self {
+		DelayedBody{delay: Box::pin(rt::time::sleep(delay)), size: bytes.len() as u64, bytes: Some(bytes)}
+	}
+}
+
+impl MessageBody for DelayedBody {
+	type Error = Infallible;
+
+	fn size(&self) -> BodySize {
+		BodySize::Sized(self.size)
+	}
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<web::Bytes, Self::Error>>> {
+		if self.bytes.is_none() {
+			return Poll::Ready(None);
+		}
+		match self.delay.as_mut().poll(cx) {
+			Poll::Ready(()) => Poll::Ready(self.bytes.take().map(Ok)),
+			Poll::Pending => Poll::Pending,
+		}
+	}
+}
+
+/// `/slow-headers?header_delay_ms=<n>&body_delay_ms=<n>`: waits before sending the response
+/// headers and again before sending the body, separating TTFB from total response time.
+pub async fn slow_headers(query: web::Query<SlowHeadersQuery>) -> Result<HttpResponse, BenchError> {
+	let header_delay = query.header_delay_ms.unwrap_or(0);
+	let body_delay = query.body_delay_ms.unwrap_or(0);
+	if header_delay > MAX_DELAY_MS || body_delay > MAX_DELAY_MS {
+		return Err(BenchError::bad_request(format!("delays must not exceed {} ms", MAX_DELAY_MS)));
+	}
+
+	rt::time::sleep(Duration::from_millis(header_delay)).await;
+	let body = format!("header_delay_ms={} body_delay_ms={}\n", header_delay, body_delay);
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::TEXT_PLAIN))
+	.body(DelayedBody::new(Duration::from_millis(body_delay), body.into())))
+}
+
+#[derive(Deserialize)]
+pub struct SlowReadQuery {
+	rate: u64,
+}
+
+/// `POST /slow-read?rate=<bytes/s>`: reads the request body no faster than `rate`, one
+/// chunk at a time. Once actix's read buffer is full the client's writes back up into the
+/// TCP window. Capped by `--max-body-size`.
+pub async fn slow_read(req: HttpRequest, query: web::Query<SlowReadQuery>, mut body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	let rate = query.rate;
+	if rate == 0 {
+		return Err(BenchError::bad_request("rate must be at least 1 byte/s").into());
+	}
+	let limit = req.app_data::<MaxBodySize>().map_or(usize::MAX, |limit| limit.0);
+
+	let start = Instant::now();
+	let mut total = 0;
+	let mut chunks = 0;
+	while let Some(chunk) = body.next().await {
+		total += chunk?.len();
+		chunks += 1;
+		if total > limit {
+			return Err(BenchError::new(StatusCode::PAYLOAD_TOO_LARGE, format!("body exceeds {} bytes", limit)).into());
+		}
+		// hold the next read until the bytes so far fit the rate
+		let due = Duration::from_secs_f64(total as f64 / rate as f64);
+		rt::time::sleep(due.saturating_sub(start.elapsed())).await;
+	}
+
+	let duration = start.elapsed();
+	let body = json!({
+		"bytes": total,
+		"chunks": chunks,
+		"rate": rate,
+		"duration_ms": duration.as_millis() as u64,
+		"effective_rate": if duration.is_zero() { 0.0 } else { total as f64 / duration.as_secs_f64() },
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
+
+#[derive(Deserialize)]
+pub struct SlowStartQuery {
+	start_rate: u64,
+	max_rate: u64,
+	ramp: u64,
+	size: Option<u64>,
+}
+
+/// Bytes a rate ramping linearly from `start` to `max` bytes/s over `ramp` has allowed by
+/// `elapsed`, the area under the rate curve.
+fn ramped_bytes(start: f64, max: f64, ramp: f64, elapsed: f64) -> f64 {
+	if elapsed < ramp {
+		start * elapsed + (max - start) * elapsed * elapsed / (2.0 * ramp)
+	} else {
+		(start + max) * ramp / 2.0 + max * (elapsed - ramp)
+	}
+}
+
+/// `/slowstart?start_rate=<bytes/s>&max_rate=<bytes/s>&ramp=<ms>&size=<bytes>`: a `size`
+/// byte body whose send rate climbs linearly from `start_rate` to `max_rate` over `ramp`
+/// milliseconds and stays there, like a connection leaving slow start or a cache warming up.
+pub async fn slowstart(query: web::Query<SlowStartQuery>) -> Result<HttpResponse, BenchError> {
+	let SlowStartQuery{start_rate, max_rate, ramp, size} = query.into_inner();
+	let size = size.unwrap_or(DEFAULT_SLOWSTART_SIZE);
+	if start_rate == 0 || max_rate > MAX_RATE || start_rate > max_rate {
+		return Err(BenchError::bad_request(format!("rates must satisfy 1 <= start_rate <= max_rate <= {}", MAX_RATE)));
+	}
+	if ramp > MAX_RAMP_MS {
+		return Err(BenchError::bad_request(format!("ramp must be at most {} ms", MAX_RAMP_MS)));
+	}
+	if size > MAX_SLOWSTART_SIZE {
+		return Err(BenchError::bad_request(format!("size must be at most {} bytes", MAX_SLOWSTART_SIZE)));
+	}
+
+	let start = Instant::now();
+	let ticker = rt::time::interval(SLOWSTART_TICK);
+	let chunks = futures_util::stream::unfold((ticker, 0u64), move |(mut ticker, sent)| async move {
+		loop {
+			if sent >= size {
+				return None;
+			}
+			// credit built up while the client stalled goes out in chunks, not one buffer
+			let allowed = ramped_bytes(start_rate as f64, max_rate as f64, ramp as f64 / 1000.0, start.elapsed().as_secs_f64());
+			let len = (allowed as u64).min(size).saturating_sub(sent).min(SLOWSTART_CHUNK as u64);
+			if len > 0 {
+				let chunk = web::Bytes::from_static(&SLOWSTART_FILL[..len as usize]);
+				return Some((Ok::<_, Infallible>(chunk), (ticker, sent + len)));
+			}
+			ticker.tick().await;
+		}
+	});
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+	.no_chunking(size)
+	.streaming(chunks))
+}
+
+/// Requests `/slow-start` has served since startup or the last `/admin/reset`.
+#[derive(Default)]
+pub struct Warmup {
+	requests: AtomicU64,
+}
+
+impl Warmup {
+	pub fn reset(&self) {
+		self.requests.store(0, Ordering::Relaxed);
+	}
+}
+
+#[derive(Deserialize)]
+pub struct WarmupQuery {
+	warmup_requests: Option<u64>,
+	ramp_ms: Option<u64>,
+}
+
+/// `/slow-start?warmup_requests=<n>&ramp_ms=<ms>`: the first `n` requests are held back by
+/// a delay that shrinks linearly from `ramp_ms` to nothing, later ones are answered at once.
+/// Mimics the warmup of a JIT compiled server, next to which a Rust server is a control group.
+pub async fn slow_start(query: web::Query<WarmupQuery>, warmup: web::Data<Warmup>) -> Result<HttpResponse, BenchError> {
+	let warmup_requests = query.warmup_requests.unwrap_or(DEFAULT_WARMUP_REQUESTS);
+	let ramp_ms = query.ramp_ms.unwrap_or(DEFAULT_WARMUP_DELAY_MS);
+	if ramp_ms > MAX_DELAY_MS {
+		return Err(BenchError::bad_request(format!("ramp_ms must be at most {}", MAX_DELAY_MS)));
+	}
+
+	let request = warmup.requests.fetch_add(1, Ordering::Relaxed);
+	let delay = if request < warmup_requests {
+		Duration::from_secs_f64(ramp_ms as f64 / 1000.0 * (warmup_requests - request) as f64 / warmup_requests as f64)
+	} else {
+		Duration::ZERO
+	};
+	rt::time::sleep(delay).await;
+
+	let body = json!({
+		"request": request + 1,
+		"warmup_requests": warmup_requests,
+		"warm": delay.is_zero(),
+		"delay_ms": delay.as_secs_f64() * 1000.0,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
@@ -0,0 +1,72 @@
+use log::*;
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
+use std::io;
+use std::net::{self, IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+/// Same listen backlog actix-server uses for the sockets it binds itself.
+pub const DEFAULT_BACKLOG: i32 = 2048;
+
+/// Socket options applied to the listeners, accepted connections inherit them from there.
+#[derive(Clone, Copy, Default)]
+pub struct SocketOptions {
+	pub send_buffer: Option<usize>,
+	pub recv_buffer: Option<usize>,
+	/// pending connections queued by the kernel before accept, `DEFAULT_BACKLOG` if unset
+	pub backlog: Option<i32>,
+	/// SO_KEEPALIVE idle time before the first probe, `--tcp-keepalive`
+	pub keepalive: Option<Duration>,
+}
+
+/// The address `--bind-interface` binds to: the interface's first IPv4 address, else its
+/// first IPv6 address that isn't link-local, which would need a scope id to bind.
+pub fn interface_ip(name: &str) -> io::Result<IpAddr> {
+	let addrs: Vec<IpAddr> = if_addrs::get_if_addrs()?.into_iter()
+		.filter(|interface| interface.name == name)
+		.filter(|interface| interface.ip().is_ipv4() || !interface.is_link_local())
+		.map(|interface| interface.ip())
+		.collect();
+	addrs.iter().find(|ip| ip.is_ipv4()).or_else(|| addrs.first()).copied()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("interface {} has no usable address", name)))
+}
+
+/// Binds a listener on the first address `address` resolves to that accepts the bind.
+pub fn bind(address: &str, options: &SocketOptions) -> io::Result<net::TcpListener> {
+	let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, format!("{} resolves to no address", address));
+	for addr in address.to_socket_addrs()? {
+		match bind_addr(addr, options) {
+			Ok(listener) => return Ok(listener),
+			Err(err) => last_err = err,
+		}
+	}
+	Err(last_err)
+}
+
+fn bind_addr(addr: SocketAddr, options: &SocketOptions) -> io::Result<net::TcpListener> {
+	let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+	socket.set_reuse_address(true)?;
+	if let Some(size) = options.send_buffer {
+		match socket.set_send_buffer_size(size) {
+			Ok(()) => info!("SO_SNDBUF on {}: requested {}, actual {}", addr, size, socket.send_buffer_size()?),
+			Err(err) => warn!("SO_SNDBUF on {}: could not set {}: {}", addr, size, err),
+		}
+	}
+	if let Some(size) = options.recv_buffer {
+		match socket.set_recv_buffer_size(size) {
+			Ok(()) => info!("SO_RCVBUF on {}: requested {}, actual {}", addr, size, socket.recv_buffer_size()?),
+			Err(err) => warn!("SO_RCVBUF on {}: could not set {}: {}", addr, size, err),
+		}
+	}
+	if let Some(idle) = options.keepalive {
+		// Linux and the BSDs copy both the flag and the idle time into accepted sockets
+		match socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle)) {
+			Ok(()) => info!("SO_KEEPALIVE on {}: idle {:?}", addr, idle),
+			Err(err) => warn!("SO_KEEPALIVE on {}: could not set idle {:?}, keepalive stays off: {}", addr, idle, err),
+		}
+	}
+	socket.bind(&addr.into())?;
+	let backlog = options.backlog.unwrap_or(DEFAULT_BACKLOG);
+	socket.listen(backlog)?;
+	info!("listen backlog on {}: {}", addr, backlog);
+	Ok(socket.into())
+}
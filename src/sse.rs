@@ -0,0 +1,212 @@
+use actix_web::{http::header, rt, web, HttpResponse};
+use dashmap::DashMap;
+use serde::Deserialize;
+use anyhow::{anyhow, Context};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::error::BenchError;
+
+/// Messages buffered per channel before slow subscribers start missing some.
+const CHANNEL_CAPACITY: usize = 1024;
+/// Idle streams get a comment this often, so disconnected subscribers are noticed.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const DEFAULT_CLOCK_INTERVAL_MS: u64 = 1000;
+const MAX_CLOCK_INTERVAL_MS: u64 = 60_000;
+/// Gap before replayed events that don't give their own `: delay_ms=`.
+const DEFAULT_REPLAY_DELAY: Duration = Duration::from_secs(1);
+const REPLAY_DELAY_COMMENT: &str = ": delay_ms=";
+const MAX_REPLAY_SPEED: f64 = 1000.0;
+
+/// Named broadcast channels behind `/sse-broadcast`, created by the first subscriber or
+/// publisher and dropped again with their last subscriber.
+#[derive(Default)]
+pub struct Broadcasts {
+	channels: DashMap<String, broadcast::Sender<Arc<str>>>,
+}
+
+impl Broadcasts {
+	fn sender(&self, channel: &str) -> broadcast::Sender<Arc<str>> {
+		self.channels.entry(channel.to_string())
+			.or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+			.clone()
+	}
+
+	/// Current subscriber count of every channel, for `/stats`.
+	pub fn subscribers(&self) -> Value {
+		self.channels.iter()
+			.map(|channel| (channel.key().clone(), json!(channel.value().receiver_count())))
+			.collect::<serde_json::Map<_, _>>()
+			.into()
+	}
+}
+
+#[derive(Deserialize)]
+pub struct BroadcastQuery {
+	channel: String,
+}
+
+/// A subscriber's receiver, removing its channel once nobody listens anymore.
+struct Subscription {
+	broadcasts: web::Data<Broadcasts>,
+	channel: String,
+	receiver: Option<broadcast::Receiver<Arc<str>>>,
+	keep_alive: rt::time::Interval,
+}
+
+impl Drop for Subscription {
+	fn drop(&mut self) {
+		drop(self.receiver.take());
+		self.broadcasts.channels.remove_if(&self.channel, |_, sender| sender.receiver_count() == 0);
+	}
+}
+
+/// Formats a message as a server-sent event, one `data:` line per message line.
+fn event(message: &str) -> web::Bytes {
+	let mut event = String::with_capacity(message.len() + 8);
+	for line in message.lines() {
+		event.push_str("data: ");
+		event.push_str(line);
+		event.push('\n');
+	}
+	event.push('\n');
+	event.into()
+}
+
+/// `GET /sse-broadcast?channel=<name>`: streams every message published to the channel
+/// as a server-sent event until the client disconnects.
+pub async fn subscribe(query: web::Query<BroadcastQuery>, broadcasts: web::Data<Broadcasts>) -> HttpResponse {
+	let channel = query.into_inner().channel;
+	let receiver = broadcasts.sender(&channel).subscribe();
+	let keep_alive = rt::time::interval_at(rt::time::Instant::now() + KEEP_ALIVE_INTERVAL, KEEP_ALIVE_INTERVAL);
+	let subscription = Subscription{broadcasts, channel, receiver: Some(receiver), keep_alive};
+
+	let events = futures_util::stream::unfold(subscription, |mut subscription| async move {
+		let receiver = subscription.receiver.as_mut()?;
+		let chunk = tokio::select! {
+			message = receiver.recv() => match message {
+				Ok(message) => event(&message),
+				// tell the subscriber it fell behind instead of silently dropping messages
+				Err(RecvError::Lagged(missed)) => format!(": lagged, {} messages missed\n\n", missed).into(),
+				Err(RecvError::Closed) => return None,
+			},
+			_ = subscription.keep_alive.tick() => web::Bytes::from_static(b": keep-alive\n\n"),
+		};
+		Some((Ok::<_, actix_web::Error>(chunk), subscription))
+	});
+
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::TEXT_EVENT_STREAM))
+	.insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+	.streaming(events)
+}
+
+/// `POST /sse-broadcast?channel=<name>`: publishes the request body to every subscriber of
+/// the channel.
+pub async fn publish(query: web::Query<BroadcastQuery>, body: web::Bytes, broadcasts: web::Data<Broadcasts>) -> Result<HttpResponse, BenchError> {
+	let message = std::str::from_utf8(&body).map_err(|_| BenchError::bad_request("message must be UTF-8 text"))?;
+	// no subscribers is not an error, the message just reaches nobody
+	let subscribers = broadcasts.sender(&query.channel).send(Arc::from(message)).unwrap_or(0);
+	broadcasts.channels.remove_if(&query.channel, |_, sender| sender.receiver_count() == 0);
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"channel": query.channel, "subscribers": subscribers}).to_string()))
+}
+
+#[derive(Deserialize)]
+pub struct ClockQuery {
+	interval_ms: Option<u64>,
+	count: Option<u64>,
+}
+
+/// `/sse-clock?interval_ms=<n>&count=<n>`: an event `{"ts":<epoch_ms>,"seq":N}` every
+/// `interval_ms`, the first right away. The stream ends after `count` events, if given.
+pub async fn clock(query: web::Query<ClockQuery>) -> Result<HttpResponse, BenchError> {
+	let interval = query.interval_ms.unwrap_or(DEFAULT_CLOCK_INTERVAL_MS);
+	if interval == 0 || interval > MAX_CLOCK_INTERVAL_MS {
+		return Err(BenchError::bad_request(format!("interval_ms must be between 1 and {}", MAX_CLOCK_INTERVAL_MS)));
+	}
+	let count = query.count.unwrap_or(u64::MAX);
+
+	let ticker = rt::time::interval(Duration::from_millis(interval));
+	let events = futures_util::stream::unfold((ticker, 0u64), move |(mut ticker, seq)| async move {
+		if seq >= count {
+			return None;
+		}
+		ticker.tick().await;
+		let ts = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_millis() as u64);
+		let chunk = event(&json!({"ts": ts, "seq": seq}).to_string());
+		Some((Ok::<_, actix_web::Error>(chunk), (ticker, seq + 1)))
+	});
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::TEXT_EVENT_STREAM))
+	.insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+	.streaming(events))
+}
+
+/// The recorded stream `/sse-replay` plays back, read from `--sse-file` at startup.
+pub struct SseReplay {
+	/// each event with the time to wait before sending it
+	events: Vec<(Duration, web::Bytes)>,
+}
+
+impl SseReplay {
+	/// Blank lines separate the events of the file, which are sent as written. An event may
+	/// start with a `: delay_ms=<n>` comment, the time since the previous one, that is not
+	/// sent; events without it follow after a second.
+	pub fn load(file_name: &str) -> anyhow::Result<Self> {
+		let text = std::fs::read_to_string(file_name).with_context(|| format!("failed to read SSE file {}", file_name))?;
+		let mut events = Vec::new();
+		let mut lines = Vec::new();
+		let mut delay = DEFAULT_REPLAY_DELAY;
+		// a final event without its blank line still counts
+		for (number, line) in text.lines().chain([""]).enumerate() {
+			if let Some(ms) = line.strip_prefix(REPLAY_DELAY_COMMENT) {
+				let ms = ms.trim().parse::<u64>().map_err(|_| anyhow!("{} line {}: invalid delay {:?}", file_name, number + 1, ms))?;
+				delay = Duration::from_millis(ms);
+			} else if !line.is_empty() {
+				lines.push(line);
+			} else if !lines.is_empty() {
+				let mut event = lines.join("\n");
+				event.push_str("\n\n");
+				events.push((delay, event.into()));
+				lines.clear();
+				delay = DEFAULT_REPLAY_DELAY;
+			}
+		}
+		if events.is_empty() {
+			return Err(anyhow!("SSE file {} has no events", file_name));
+		}
+		Ok(SseReplay{events})
+	}
+}
+
+#[derive(Deserialize)]
+pub struct ReplayQuery {
+	speed: Option<f64>,
+}
+
+/// `/sse-replay?speed=<factor>`: plays the `--sse-file` recording back from its start, with
+/// its delays divided by `speed`, default 1.0. The stream ends with the last event.
+pub async fn replay(query: web::Query<ReplayQuery>, replay: Option<web::Data<SseReplay>>) -> Result<HttpResponse, BenchError> {
+	let replay = replay.ok_or_else(|| BenchError::new(actix_web::http::StatusCode::NOT_FOUND, "no --sse-file is configured"))?;
+	let speed = query.speed.unwrap_or(1.0);
+	if !(speed > 0.0 && speed <= MAX_REPLAY_SPEED) {
+		return Err(BenchError::bad_request(format!("speed must be above 0 and at most {}", MAX_REPLAY_SPEED)));
+	}
+
+	let events = futures_util::stream::unfold((replay, 0usize), move |(replay, index)| async move {
+		let (delay, event) = replay.events.get(index)?.clone();
+		rt::time::sleep(delay.div_f64(speed)).await;
+		Some((Ok::<_, actix_web::Error>(event), (replay, index + 1)))
+	});
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::TEXT_EVENT_STREAM))
+	.insert_header(header::CacheControl(vec![header::CacheDirective::NoCache]))
+	.streaming(events))
+}
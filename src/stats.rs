@@ -0,0 +1,220 @@
+use actix_web::{
+	body::MessageBody,
+	dev::{ServiceRequest, ServiceResponse},
+	http::header,
+	middleware::Next,
+	web, HttpRequest, HttpResponse,
+};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::admin::Listeners;
+use crate::connection::OverflowStrategy;
+use crate::memory::MemoryGuard;
+use crate::sse::Broadcasts;
+use crate::watchdog::Watchdog;
+
+/// Counters reported by `/stats`.
+#[derive(Default)]
+pub struct Stats {
+	pub connections: ConnectionStats,
+	pub ws_bench: WsBenchStats,
+	pub upstream: UpstreamStats,
+	pub long_poll: LongPollStats,
+	pub continue_stream: ContinueStreamStats,
+	pub in_flight: InFlightStats,
+	pub watchdog: WatchdogStats,
+}
+
+/// Requests inside the app, from the outermost middleware until the response is handed
+/// back. actix runs a worker's requests concurrently, so whatever queues in the server
+/// shows up here, waiting on a lock, a pool or the worker's event loop.
+#[derive(Default)]
+pub struct InFlightStats {
+	pub total: AtomicUsize,
+	pub peak: AtomicUsize,
+	/// one gauge per app instance, that is per worker of every listener
+	workers: Mutex<Vec<Arc<AtomicUsize>>>,
+}
+
+/// The in-flight gauge of the worker an app instance runs on.
+#[derive(Clone)]
+pub struct WorkerInFlight {
+	stats: web::Data<Stats>,
+	gauge: Arc<AtomicUsize>,
+}
+
+impl WorkerInFlight {
+	/// Adds a gauge for a new app instance, called from the app factory.
+	pub fn register(stats: &web::Data<Stats>) -> Self {
+		let gauge = Arc::new(AtomicUsize::new(0));
+		stats.in_flight.workers.lock().unwrap().push(gauge.clone());
+		WorkerInFlight{stats: stats.clone(), gauge}
+	}
+}
+
+/// Counts the request out again however the request ends, cancellation included.
+struct InFlightGuard<'a>(&'a WorkerInFlight);
+
+impl Drop for InFlightGuard<'_> {
+	fn drop(&mut self) {
+		self.0.gauge.fetch_sub(1, Ordering::Relaxed);
+		self.0.stats.in_flight.total.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+/// Keeps the per-worker in-flight gauges of `/stats` up to date.
+pub async fn track_in_flight(
+	req: ServiceRequest,
+	next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+	let Some(worker) = req.app_data::<WorkerInFlight>().cloned() else {
+		return next.call(req).await;
+	};
+	worker.gauge.fetch_add(1, Ordering::Relaxed);
+	let total = worker.stats.in_flight.total.fetch_add(1, Ordering::Relaxed) + 1;
+	worker.stats.in_flight.peak.fetch_max(total, Ordering::Relaxed);
+	let _guard = InFlightGuard(&worker);
+	next.call(req).await
+}
+
+/// How late the workers' watchdog timers fired, see `watchdog`.
+#[derive(Default)]
+pub struct WatchdogStats {
+	pub max_latency_us: AtomicU64,
+	/// wake ups later than `--watchdog-threshold`
+	pub stalls: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct ContinueStreamStats {
+	pub active: AtomicUsize,
+	pub rejected: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct LongPollStats {
+	pub waiting: AtomicUsize,
+	pub delivered: AtomicU64,
+	pub timeouts: AtomicU64,
+	pub disconnected: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct ConnectionStats {
+	pub active: AtomicUsize,
+	pub accepted: AtomicU64,
+	/// requests answered with 503 under `--overflow-strategy accept-then-503`
+	pub overflow_rejected: AtomicU64,
+}
+
+#[derive(Default)]
+pub struct WsBenchStats {
+	pub active: AtomicUsize,
+	pub messages_sent: AtomicU64,
+	pub bytes_sent: AtomicU64,
+}
+
+/// Requests forwarded to `--upstream`, latency is the time until the upstream answered.
+#[derive(Default)]
+pub struct UpstreamStats {
+	pub requests: AtomicU64,
+	pub errors: AtomicU64,
+	pub latency_us_total: AtomicU64,
+	pub latency_us_max: AtomicU64,
+}
+
+impl UpstreamStats {
+	pub fn record(&self, latency: Duration, ok: bool) {
+		let latency = latency.as_micros() as u64;
+		self.requests.fetch_add(1, Ordering::Relaxed);
+		if !ok {
+			self.errors.fetch_add(1, Ordering::Relaxed);
+		}
+		self.latency_us_total.fetch_add(latency, Ordering::Relaxed);
+		self.latency_us_max.fetch_max(latency, Ordering::Relaxed);
+	}
+}
+
+/// Keeps a gauge incremented for as long as the guard lives, e.g. the lifetime of a stream.
+pub struct Tracked {
+	stats: web::Data<Stats>,
+	gauge: fn(&Stats) -> &AtomicUsize,
+}
+
+impl Tracked {
+	pub fn new(stats: web::Data<Stats>, gauge: fn(&Stats) -> &AtomicUsize) -> Self {
+		gauge(&stats).fetch_add(1, Ordering::Relaxed);
+		Tracked{stats, gauge}
+	}
+}
+
+impl Drop for Tracked {
+	fn drop(&mut self) {
+		(self.gauge)(&self.stats).fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+impl Stats {
+	pub fn snapshot(&self) -> Value {
+		let upstream_requests = self.upstream.requests.load(Ordering::Relaxed);
+		json!({
+			"connections": {
+				"active": self.connections.active.load(Ordering::Relaxed),
+				"accepted": self.connections.accepted.load(Ordering::Relaxed),
+				"overflow_rejected": self.connections.overflow_rejected.load(Ordering::Relaxed),
+			},
+			"ws_bench": {
+				"active": self.ws_bench.active.load(Ordering::Relaxed),
+				"messages_sent": self.ws_bench.messages_sent.load(Ordering::Relaxed),
+				"bytes_sent": self.ws_bench.bytes_sent.load(Ordering::Relaxed),
+			},
+			"long_poll": {
+				"waiting": self.long_poll.waiting.load(Ordering::Relaxed),
+				"delivered": self.long_poll.delivered.load(Ordering::Relaxed),
+				"timeouts": self.long_poll.timeouts.load(Ordering::Relaxed),
+				"disconnected": self.long_poll.disconnected.load(Ordering::Relaxed),
+			},
+			"continue_stream": {
+				"active": self.continue_stream.active.load(Ordering::Relaxed),
+				"rejected": self.continue_stream.rejected.load(Ordering::Relaxed),
+			},
+			"in_flight": {
+				"total": self.in_flight.total.load(Ordering::Relaxed),
+				"peak": self.in_flight.peak.load(Ordering::Relaxed),
+				"workers": self.in_flight.workers.lock().unwrap().iter().map(|gauge| gauge.load(Ordering::Relaxed)).collect::<Vec<_>>(),
+			},
+			"watchdog": {
+				"max_scheduling_latency_us": self.watchdog.max_latency_us.load(Ordering::Relaxed),
+				"stalls": self.watchdog.stalls.load(Ordering::Relaxed),
+			},
+			"upstream": {
+				"requests": upstream_requests,
+				"errors": self.upstream.errors.load(Ordering::Relaxed),
+				"latency_us_avg": self.upstream.latency_us_total.load(Ordering::Relaxed).checked_div(upstream_requests).unwrap_or(0),
+				"latency_us_max": self.upstream.latency_us_max.load(Ordering::Relaxed),
+			},
+		})
+	}
+}
+
+pub async fn stats(req: HttpRequest, stats: web::Data<Stats>, broadcasts: web::Data<Broadcasts>) -> HttpResponse {
+	let mut snapshot = stats.snapshot();
+	snapshot["sse_broadcast"] = json!({"subscribers": broadcasts.subscribers()});
+	snapshot["connections"]["overflow_strategy"] = json!(req.app_data::<OverflowStrategy>().copied().unwrap_or_default().to_string());
+	if let Some(guard) = req.app_data::<web::Data<MemoryGuard>>() {
+		snapshot["memory"] = guard.snapshot();
+	}
+	if let Some(listeners) = req.app_data::<web::Data<Listeners>>() {
+		snapshot["connections"]["accepting_paused"] = json!(listeners.is_paused());
+	}
+	if let Some(watchdog) = req.app_data::<Watchdog>() {
+		snapshot["watchdog"]["interval_ms"] = json!(watchdog.interval.as_millis() as u64);
+		snapshot["watchdog"]["threshold_ms"] = json!(watchdog.threshold.as_millis() as u64);
+	}
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(snapshot.to_string())
+}
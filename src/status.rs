@@ -0,0 +1,129 @@
+use actix_web::{http::{header, StatusCode}, web, HttpResponse};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::error::BenchError;
+
+/// Position shared by every `/multi-status` request, whatever sequence they ask for.
+#[derive(Default)]
+pub struct MultiStatus {
+	position: AtomicU64,
+}
+
+impl MultiStatus {
+	pub fn reset(&self) {
+		self.position.store(0, Ordering::Relaxed);
+	}
+}
+
+#[derive(Deserialize)]
+pub struct MultiStatusQuery {
+	sequence: String,
+}
+
+fn parse_sequence(sequence: &str) -> Result<Vec<StatusCode>, BenchError> {
+	sequence.split(',').map(|code| {
+		code.trim().parse::<u16>().ok()
+			.and_then(final_status)
+			.ok_or_else(|| BenchError::bad_request(format!("invalid status code '{}' in sequence, expected 200-599", code)))
+	}).collect()
+}
+
+/// `/multi-status?sequence=200,200,500,503`: answers successive requests with the listed
+/// statuses in order, wrapping around at the end.
+pub async fn multi_status(query: web::Query<MultiStatusQuery>, state: web::Data<MultiStatus>) -> Result<HttpResponse, BenchError> {
+	let sequence = parse_sequence(&query.sequence)?;
+	let position = state.position.fetch_add(1, Ordering::Relaxed) % sequence.len() as u64;
+	let status = sequence[position as usize];
+
+	Ok(HttpResponse::build(status)
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"status": status.as_u16(), "position": position}).to_string()))
+}
+
+/// `code` if a response can be sent with it, 1xx are interim and above 599 is undefined.
+fn final_status(code: u16) -> Option<StatusCode> {
+	StatusCode::from_u16(code).ok().filter(|status| (200..600).contains(&status.as_u16()))
+}
+
+/// Body sent for a status code, `--status-body <code>=<file|inline>`.
+#[derive(Clone)]
+pub struct StatusBody {
+	content_type: mime::Mime,
+	bytes: web::Bytes,
+}
+
+impl StatusBody {
+	pub fn response(&self, status: StatusCode) -> HttpResponse {
+		HttpResponse::build(status)
+		.insert_header(header::ContentType(self.content_type.clone()))
+		.body(self.bytes.clone())
+	}
+}
+
+/// Bodies of `/status/{code}` and of error responses with a configured status.
+#[derive(Default)]
+pub struct StatusBodies {
+	bodies: HashMap<StatusCode, StatusBody>,
+}
+
+pub(crate) fn file_content_type(path: &str) -> mime::Mime {
+	match path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase()).as_deref() {
+		Some("json") => mime::APPLICATION_JSON,
+		Some("html" | "htm") => mime::TEXT_HTML_UTF_8,
+		Some("xml") => mime::TEXT_XML,
+		Some("txt") => mime::TEXT_PLAIN_UTF_8,
+		_ => mime::APPLICATION_OCTET_STREAM,
+	}
+}
+
+impl StatusBodies {
+	/// Parses `<code>=<body>` specs, a body starting with `@` names the file to read it from
+	/// and anything else is sent as is, as JSON if it parses as JSON.
+	pub fn parse<'a>(specs: impl IntoIterator<Item = &'a str>) -> Result<Self> {
+		let mut bodies = HashMap::new();
+		for spec in specs {
+			let (code, body) = spec.split_once('=')
+				.ok_or_else(|| anyhow!("status body {:?} is not <code>=<file|inline>", spec))?;
+			let status = code.trim().parse::<u16>().ok()
+				.and_then(final_status)
+				.ok_or_else(|| anyhow!("invalid status code {:?} in status body {:?}, expected 200-599", code, spec))?;
+			let body = match body.strip_prefix('@') {
+				Some(path) => StatusBody{
+					content_type: file_content_type(path),
+					bytes: std::fs::read(path).with_context(|| format!("failed to read the {} status body from {}", status.as_u16(), path))?.into(),
+				},
+				None => StatusBody{
+					content_type: if serde_json::from_str::<serde::de::IgnoredAny>(body).is_ok() { mime::APPLICATION_JSON } else { mime::TEXT_PLAIN_UTF_8 },
+					bytes: web::Bytes::copy_from_slice(body.as_bytes()),
+				},
+			};
+			if bodies.insert(status, body).is_some() {
+				bail!("status {} has more than one body", status.as_u16());
+			}
+		}
+		Ok(StatusBodies{bodies})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.bodies.is_empty()
+	}
+
+	pub fn get(&self, status: StatusCode) -> Option<&StatusBody> {
+		self.bodies.get(&status)
+	}
+}
+
+/// `/status/{code}`: answers any method with `code` and the body `--status-body` set for it,
+/// an empty one otherwise.
+pub async fn status(code: web::Path<u16>, bodies: Option<web::Data<StatusBodies>>) -> Result<HttpResponse, BenchError> {
+	let status = final_status(*code)
+		.ok_or_else(|| BenchError::bad_request(format!("invalid status code {}, expected 200-599", code)))?;
+	Ok(match bodies.as_ref().and_then(|bodies| bodies.get(status)) {
+		Some(body) => body.response(status),
+		None => HttpResponse::build(status).finish(),
+	})
+}
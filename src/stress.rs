@@ -0,0 +1,53 @@
+use actix_web::{http::header, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Instant;
+
+use crate::error::BenchError;
+
+/// Upper bound of memory a single stress request may allocate.
+const MAX_TOTAL_ALLOC: usize = 512 * 1024 * 1024;
+/// Upper bound of the objects a single stress request may allocate, whatever their size.
+const MAX_ALLOC_COUNT: usize = 10_000_000;
+
+#[derive(Deserialize)]
+pub struct StressGcQuery {
+	alloc_count: usize,
+	object_size: usize,
+}
+
+/// `/stress-gc?alloc_count=<n>&object_size=<bytes>`: allocates `n` buffers, hashes them so the
+/// work can't be optimized away, then drops them all.
+pub async fn stress_gc(query: web::Query<StressGcQuery>) -> Result<HttpResponse, actix_web::Error> {
+	let StressGcQuery{alloc_count, object_size} = query.into_inner();
+	if alloc_count > MAX_ALLOC_COUNT {
+		return Err(BenchError::bad_request(format!("alloc_count must be at most {}", MAX_ALLOC_COUNT)).into());
+	}
+	// every object also costs its `Vec` header in the outer vector, even when empty
+	let per_object = object_size.saturating_add(std::mem::size_of::<Vec<u8>>());
+	match alloc_count.checked_mul(per_object) {
+		Some(total) if total <= MAX_TOTAL_ALLOC => {}
+		_ => return Err(BenchError::bad_request(format!("alloc_count * (object_size + {}) must not exceed {} bytes", std::mem::size_of::<Vec<u8>>(), MAX_TOTAL_ALLOC)).into()),
+	}
+
+	// keep the allocator churn off the worker thread so other requests keep being served
+	let (hash, elapsed) = web::block(move || {
+		let start = Instant::now();
+		let objects: Vec<Vec<u8>> = (0..alloc_count).map(|i| vec![i as u8; object_size]).collect();
+		let mut hasher = DefaultHasher::new();
+		objects.hash(&mut hasher);
+		drop(objects);
+		(hasher.finish(), start.elapsed())
+	}).await?;
+
+	let body = json!({
+		"allocs": alloc_count,
+		"hash": hash,
+		"duration_us": elapsed.as_micros() as u64,
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
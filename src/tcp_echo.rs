@@ -0,0 +1,58 @@
+// Raw TCP echo listener, `--tcp-echo`. No HTTP at all, its round trips are the transport
+// floor the HTTP numbers can be compared against.
+use log::{debug, info, warn};
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::socket::{self, SocketOptions};
+
+/// Bytes read per connection before they are written back, the most one connection holds.
+const BUFFER_SIZE: usize = 64 * 1024;
+/// Pause after a failed accept, running out of file descriptors would otherwise spin.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Binds the echo listener with the same socket options as the HTTP ones.
+pub fn bind(address: &str, options: &SocketOptions) -> io::Result<TcpListener> {
+	let listener = socket::bind(address, options)?;
+	listener.set_nonblocking(true)?;
+	let listener = TcpListener::from_std(listener)?;
+	info!("tcp echo server listen on {}", listener.local_addr()?);
+	Ok(listener)
+}
+
+/// Accepts connections on `listener` and echoes each one until its client closes it.
+pub async fn serve(listener: TcpListener) {
+	loop {
+		match listener.accept().await {
+			Ok((stream, peer)) => {
+				actix_web::rt::spawn(async move {
+					match echo(stream).await {
+						Ok(bytes) => debug!("tcp echo connection from {} closed after {} bytes", peer, bytes),
+						Err(err) => debug!("tcp echo connection from {} failed: {}", peer, err),
+					}
+				});
+			}
+			Err(err) => {
+				warn!("tcp echo accept failed: {}", err);
+				actix_web::rt::time::sleep(ACCEPT_BACKOFF).await;
+			}
+		}
+	}
+}
+
+async fn echo(mut stream: TcpStream) -> io::Result<u64> {
+	// round trips are what's measured here, don't let Nagle hold small writes back
+	stream.set_nodelay(true)?;
+	let mut buffer = vec![0; BUFFER_SIZE];
+	let mut total = 0;
+	loop {
+		let read = stream.read(&mut buffer).await?;
+		if read == 0 {
+			return Ok(total);
+		}
+		stream.write_all(&buffer[..read]).await?;
+		total += read as u64;
+	}
+}
@@ -0,0 +1,93 @@
+use actix_web::{
+	http::{header, StatusCode},
+	web, HttpRequest, HttpResponse,
+};
+use anyhow::{anyhow, Context, Result};
+use handlebars::Handlebars;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{BenchError, REQUEST_ID_HEADER};
+use crate::status::file_content_type;
+
+const TEMPLATE_NAME: &str = "body";
+/// Seeded random integers available to `{{#each random.ints}}`.
+const RANDOM_INTS: usize = 16;
+
+/// The `--body-template` Handlebars template, compiled once and rendered by `/template`.
+pub struct BodyTemplate {
+	registry: Handlebars<'static>,
+	content_type: mime::Mime,
+}
+
+impl BodyTemplate {
+	/// Compiles `file_name`, a syntax error stops the server from starting. The content type
+	/// comes from the extension left once `.hbs` is stripped, `body.json.hbs` is JSON.
+	pub fn load(file_name: &str) -> Result<Self> {
+		let source = std::fs::read_to_string(file_name).with_context(|| format!("failed to read body template {}", file_name))?;
+		// not strict, a query parameter the request leaves out renders as nothing
+		let mut registry = Handlebars::new();
+		registry.register_template_string(TEMPLATE_NAME, source)
+			.map_err(|err| anyhow!("body template {} is invalid: {}", file_name, err))?;
+		let name = file_name.strip_suffix(".hbs").or_else(|| file_name.strip_suffix(".handlebars")).unwrap_or(file_name);
+		let mut content_type = file_content_type(name);
+		if content_type == mime::APPLICATION_OCTET_STREAM {
+			content_type = mime::TEXT_PLAIN_UTF_8;
+		}
+		// HTML escaping would turn the quotes of a JSON string into &quot;
+		if content_type != mime::TEXT_HTML_UTF_8 {
+			registry.register_escape_fn(handlebars::no_escape);
+		}
+		Ok(BodyTemplate{registry, content_type})
+	}
+
+	fn render(&self, context: &Value) -> Result<String, BenchError> {
+		self.registry.render(TEMPLATE_NAME, context)
+			.map_err(|err| BenchError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("failed to render the body template: {}", err)))
+	}
+}
+
+/// What a template can refer to: `request_id`, `timestamp_ms`, `date`, `method`, `path`,
+/// `query.<name>`, `seed` and the `random` values drawn from it.
+fn context(req: &HttpRequest, mut query: BTreeMap<String, String>) -> Result<Value, BenchError> {
+	let seed = match query.remove("seed") {
+		Some(seed) => seed.parse::<u64>().map_err(|_| BenchError::bad_request("seed must be an unsigned integer"))?,
+		None => rand::thread_rng().gen(),
+	};
+	let mut rng = StdRng::seed_from_u64(seed);
+	let request_id = req.headers().get(REQUEST_ID_HEADER)
+		.and_then(|value| value.to_str().ok())
+		.map_or_else(|| uuid::Builder::from_random_bytes(rng.gen()).into_uuid().to_string(), str::to_string);
+	let now = SystemTime::now();
+
+	Ok(json!({
+		"request_id": request_id,
+		"timestamp_ms": now.duration_since(UNIX_EPOCH).map_or(0, |now| now.as_millis() as u64),
+		"date": header::HttpDate::from(now).to_string(),
+		"method": req.method().as_str(),
+		"path": req.path(),
+		"query": query,
+		"seed": seed,
+		"random": {
+			"int": rng.gen_range(0..1_000_000u32),
+			"float": rng.gen::<f64>(),
+			"bool": rng.gen::<bool>(),
+			"hex": format!("{:016x}", rng.gen::<u64>()),
+			"uuid": uuid::Builder::from_random_bytes(rng.gen()).into_uuid().to_string(),
+			"ints": (0..RANDOM_INTS).map(|_| rng.gen_range(0..1_000_000u32)).collect::<Vec<_>>(),
+		},
+	}))
+}
+
+/// `/template?seed=<n>&<name>=<value>...`: the `--body-template` rendered for this request.
+/// The same `seed` gives the same random values, other query parameters are passed to the
+/// template as `query`.
+pub async fn template(req: HttpRequest, query: web::Query<BTreeMap<String, String>>, template: Option<web::Data<BodyTemplate>>) -> Result<HttpResponse, BenchError> {
+	let template = template.ok_or_else(|| BenchError::new(StatusCode::NOT_FOUND, "no --body-template is configured"))?;
+	let body = template.render(&context(&req, query.into_inner())?)?;
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(template.content_type.clone()))
+	.body(body))
+}
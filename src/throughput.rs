@@ -0,0 +1,46 @@
+use actix_web::{http::header, web, HttpResponse};
+use futures_util::StreamExt;
+use serde::Deserialize;
+
+use crate::error::BenchError;
+
+const DEFAULT_SIZE: u64 = 1024;
+const MAX_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+const CHUNK: usize = 64 * 1024;
+
+/// Every chunk of a `/throughput` response is a slice of this, nothing is copied or filled.
+static ZEROES: [u8; CHUNK] = [0; CHUNK];
+
+#[derive(Deserialize)]
+pub struct ThroughputQuery {
+	size: Option<u64>,
+}
+
+/// `GET /throughput?size=<bytes>`: exactly `size` zero bytes and nothing else, so the
+/// server spends its time on HTTP alone.
+pub async fn send(query: web::Query<ThroughputQuery>) -> Result<HttpResponse, BenchError> {
+	let size = query.size.unwrap_or(DEFAULT_SIZE);
+	if size > MAX_SIZE {
+		return Err(BenchError::bad_request(format!("size must be at most {} bytes", MAX_SIZE)));
+	}
+	let chunks = futures_util::stream::unfold(size, |left| async move {
+		if left == 0 {
+			return None;
+		}
+		let len = left.min(CHUNK as u64) as usize;
+		Some((Ok::<_, actix_web::Error>(web::Bytes::from_static(&ZEROES[..len])), left - len as u64))
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_OCTET_STREAM))
+	.no_chunking(size)
+	.streaming(chunks))
+}
+
+/// `POST /throughput`: reads the body, however large, and drops it chunk by chunk. The
+/// answer is an empty 200.
+pub async fn receive(mut body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	while let Some(chunk) = body.next().await {
+		chunk?;
+	}
+	Ok(HttpResponse::Ok().finish())
+}
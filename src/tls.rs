@@ -0,0 +1,486 @@
+use actix_web::rt::net::TcpStream;
+use anyhow::{anyhow, Context, Result};
+use std::{any::Any, net::SocketAddr, time::Duration};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, RawFd};
+
+#[cfg(feature = "with_openssl")]
+use log::debug;
+#[cfg(feature = "with_openssl")]
+use openssl::{ex_data::Index, ssl::{ClientHelloResponse, NameType, SniError, Ssl, SslAcceptorBuilder, SslAlert, SslFiletype, SslVerifyMode}, x509::X509Name};
+#[cfg(feature = "with_openssl")]
+use std::{sync::OnceLock, time::Instant};
+
+#[cfg(not(feature = "with_openssl"))]
+pub use self::rustls_server::*;
+
+/// Reads a PEM file, catching the usual setup mistakes of a missing, empty or non-PEM file
+/// before a TLS library reports them less clearly.
+pub fn read_pem(file_name: &str, what: &str) -> Result<Vec<u8>> {
+	let pem = std::fs::read(file_name).with_context(|| format!("failed to read {} file {}", what, file_name))?;
+	if pem.iter().all(u8::is_ascii_whitespace) {
+		return Err(anyhow!("{} file {} is empty", what, file_name));
+	}
+	if !pem.windows(11).any(|window| window == b"-----BEGIN ") {
+		return Err(anyhow!("{} file {} is not a valid PEM file", what, file_name));
+	}
+	Ok(pem)
+}
+
+/// The config `HttpServer` would give the app, for the listeners that serve it without one.
+/// actix-web keeps its constructor private, this is the one actix-test uses, which is why
+/// Cargo.toml pins the exact actix-web version.
+#[cfg(any(not(feature = "with_openssl"), feature = "http3"))]
+pub fn app_config(addr: SocketAddr) -> actix_web::dev::AppConfig {
+	actix_web::dev::AppConfig::__priv_test_new(true, addr.to_string(), addr)
+}
+
+/// Loads the certificate chain and private key (any type openssl reads from PEM) into the
+/// acceptor, naming the offending file on failure.
+#[cfg(feature = "with_openssl")]
+pub fn load_openssl_identity(builder: &mut SslAcceptorBuilder, cert_file_name: &str, key_file_name: &str) -> Result<()> {
+	read_pem(key_file_name, "private key")?;
+	builder.set_private_key_file(key_file_name, SslFiletype::PEM)
+		.with_context(|| format!("no usable private key found in {}", key_file_name))?;
+	read_pem(cert_file_name, "certificate")?;
+	builder.set_certificate_chain_file(cert_file_name)
+		.with_context(|| format!("no certificates found in {}", cert_file_name))?;
+	builder.check_private_key()
+		.with_context(|| format!("private key {} does not match certificate {}", key_file_name, cert_file_name))?;
+	Ok(())
+}
+
+/// Requires every client to present a certificate issued by one of the CAs in `ca_file_name`.
+#[cfg(feature = "with_openssl")]
+pub fn load_openssl_client_ca(builder: &mut SslAcceptorBuilder, ca_file_name: &str) -> Result<()> {
+	read_pem(ca_file_name, "client CA")?;
+	builder.set_ca_file(ca_file_name)
+		.with_context(|| format!("no usable CA certificates found in {}", ca_file_name))?;
+	builder.set_client_ca_list(X509Name::load_client_ca_file(ca_file_name)
+		.with_context(|| format!("no usable CA certificates found in {}", ca_file_name))?);
+	builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+	Ok(())
+}
+
+/// Index of the handshake start time stored on each openssl connection.
+#[cfg(feature = "with_openssl")]
+fn handshake_start_index() -> Index<Ssl, Instant> {
+	static INDEX: OnceLock<Index<Ssl, Instant>> = OnceLock::new();
+	*INDEX.get_or_init(|| Ssl::new_ex_index().expect("openssl ex data index"))
+}
+
+/// Stamps every connection when its ClientHello arrives, so the handshake can be timed once
+/// the connection is handed to the http service.
+#[cfg(feature = "with_openssl")]
+pub fn time_openssl_handshakes(builder: &mut SslAcceptorBuilder) {
+	let index = handshake_start_index();
+	builder.set_client_hello_callback(move |ssl, _| {
+		ssl.set_ex_data(index, Instant::now());
+		Ok(ClientHelloResponse::SUCCESS)
+	});
+}
+
+/// The server names TLS clients must ask for, `--sni-required` and `--sni-hostname`.
+#[derive(Clone, Default)]
+pub struct SniPolicy {
+	pub required: bool,
+	/// implies `required`
+	pub hostname: Option<String>,
+}
+
+impl SniPolicy {
+	pub fn is_enabled(&self) -> bool {
+		self.required || self.hostname.is_some()
+	}
+
+	pub fn allows(&self, server_name: Option<&str>) -> bool {
+		match (server_name, &self.hostname) {
+			(Some(name), Some(hostname)) => name.eq_ignore_ascii_case(hostname),
+			(Some(_), None) => true,
+			(None, _) => !self.is_enabled(),
+		}
+	}
+}
+
+/// Rejects handshakes `policy` doesn't allow with an `unrecognized_name` alert.
+#[cfg(feature = "with_openssl")]
+pub fn check_openssl_sni(builder: &mut SslAcceptorBuilder, policy: SniPolicy) {
+	builder.set_servername_callback(move |ssl, alert| {
+		let server_name = ssl.servername(NameType::HOST_NAME);
+		if policy.allows(server_name) {
+			return Ok(());
+		}
+		debug!("rejecting TLS handshake for server name {:?}", server_name);
+		*alert = SslAlert::UNRECOGNIZED_NAME;
+		Err(SniError::ALERT_FATAL)
+	});
+}
+
+/// A completed TLS handshake.
+#[derive(Clone, Copy)]
+pub struct Handshake {
+	pub version: &'static str,
+	pub duration: Duration,
+}
+
+/// The verified certificate a client presented under `--client-ca`.
+pub struct ClientCert {
+	pub subject: String,
+	pub issuer: String,
+	pub serial: String,
+	pub not_after: String,
+	pub not_after_unix: i64,
+}
+
+impl ClientCert {
+	/// Both TLS backends hand the certificate over as DER, read it the same way for both.
+	fn from_der(der: &[u8]) -> Option<Self> {
+		let (_, cert) = x509_parser::parse_x509_certificate(der).ok()?;
+		let not_after = cert.validity().not_after;
+		Some(ClientCert{
+			subject: cert.subject().to_string(),
+			issuer: cert.issuer().to_string(),
+			serial: cert.raw_serial_as_string(),
+			not_after: not_after.to_rfc2822().unwrap_or_else(|_| not_after.to_string()),
+			not_after_unix: not_after.timestamp(),
+		})
+	}
+}
+
+/// What is known about a connection when the http service takes it over.
+pub struct ConnectInfo {
+	pub peer: Option<SocketAddr>,
+	pub handshake: Option<Handshake>,
+	pub client_cert: Option<ClientCert>,
+	/// the TCP socket, valid for as long as the connection is open
+	#[cfg(unix)]
+	pub fd: Option<RawFd>,
+}
+
+/// Describes the stream `HttpServer::on_connect` hands over, plain TCP or openssl.
+pub fn connect_info(conn: &dyn Any) -> ConnectInfo {
+	#[cfg(feature = "with_openssl")]
+	if let Some(stream) = conn.downcast_ref::<actix_tls::accept::openssl::TlsStream<TcpStream>>() {
+		let ssl = stream.ssl();
+		let handshake = ssl.ex_data(handshake_start_index()).map(|start| Handshake{
+			version: ssl.version_str(),
+			duration: start.elapsed(),
+		});
+		let client_cert = ssl.peer_certificate()
+			.and_then(|cert| cert.to_der().ok())
+			.and_then(|der| ClientCert::from_der(&der));
+		let tcp = stream.get_ref();
+		return ConnectInfo{
+			peer: tcp.peer_addr().ok(),
+			handshake,
+			client_cert,
+			#[cfg(unix)]
+			fd: Some(tcp.as_raw_fd()),
+		};
+	}
+	let tcp = conn.downcast_ref::<TcpStream>();
+	ConnectInfo{
+		peer: tcp.and_then(|tcp| tcp.peer_addr().ok()),
+		handshake: None,
+		client_cert: None,
+		#[cfg(unix)]
+		fd: tcp.map(|tcp| tcp.as_raw_fd()),
+	}
+}
+
+/// The rustls listener runs its own accept pipeline, as actix-web gives no hook between
+/// accepting a socket and finishing its handshake.
+#[cfg(not(feature = "with_openssl"))]
+mod rustls_server {
+	use actix_http::{body::MessageBody, HttpService, Protocol, Request, Response};
+	use actix_service::{apply_fn_factory, map_config, IntoServiceFactory, Service, ServiceFactory, ServiceFactoryExt};
+	use actix_tls::accept::{rustls_0_20::{Acceptor, AcceptorService, TlsStream}, TlsError};
+	use actix_web::{dev::{AppConfig, Extensions, Server}, rt::net::TcpStream};
+	use anyhow::{anyhow, Context as _, Result};
+	use arc_swap::ArcSwap;
+	use log::{debug, info, warn};
+	use rustls::{server::{AllowAnyAuthenticatedClient, ClientHello, ResolvesServerCert}, sign::{self, CertifiedKey}, Certificate, PrivateKey, ProtocolVersion, RootCertStore, ServerConfig};
+	use rustls_pemfile::Item;
+	use std::{fmt, fs, io, net, pin::Pin, sync::Arc, task::{Context, Poll}, time::{Duration, Instant, SystemTime}};
+	use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+	#[cfg(unix)]
+	use tokio::signal::unix::{signal, SignalKind};
+
+	#[cfg(unix)]
+	use std::os::fd::AsRawFd;
+
+	use super::{read_pem, ClientCert, ConnectInfo, Handshake, SniPolicy};
+
+	/// How often the certificate files are checked for changes.
+	const CERT_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+	/// Serves the current certificate and lets it be swapped for new connections without
+	/// touching the listener or connections already established.
+	pub struct CertResolver {
+		cert_file_name: String,
+		key_file_name: String,
+		current: ArcSwap<CertifiedKey>,
+		sni: SniPolicy,
+	}
+
+	impl CertResolver {
+		pub fn load(cert_file_name: &str, key_file_name: &str) -> Result<Self> {
+			let key = load_certified_key(cert_file_name, key_file_name)?;
+			Ok(CertResolver{
+				cert_file_name: cert_file_name.to_string(),
+				key_file_name: key_file_name.to_string(),
+				current: ArcSwap::from_pointee(key),
+				sni: SniPolicy::default(),
+			})
+		}
+
+		/// Resolves no certificate for handshakes `policy` doesn't allow, failing them. rustls
+		/// 0.20 answers that with an `access_denied` alert rather than `unrecognized_name`.
+		pub fn with_sni_policy(mut self, policy: SniPolicy) -> Self {
+			self.sni = policy;
+			self
+		}
+
+		/// Swaps in the certificate currently on disk, keeping the old one if it doesn't load.
+		pub fn reload(&self) {
+			match load_certified_key(&self.cert_file_name, &self.key_file_name) {
+				Ok(key) => {
+					self.current.store(Arc::new(key));
+					info!("reloaded TLS certificate from {}", self.cert_file_name);
+				}
+				Err(err) => warn!("TLS certificate reload failed, keeping the current one: {}", err),
+			}
+		}
+
+		fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+			let cert = fs::metadata(&self.cert_file_name).and_then(|meta| meta.modified()).ok()?;
+			let key = fs::metadata(&self.key_file_name).and_then(|meta| meta.modified()).ok()?;
+			Some((cert, key))
+		}
+	}
+
+	impl ResolvesServerCert for CertResolver {
+		fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+			if !self.sni.allows(client_hello.server_name()) {
+				debug!("rejecting TLS handshake for server name {:?}", client_hello.server_name());
+				return None;
+			}
+			Some(self.current.load_full())
+		}
+	}
+
+	fn read_pem_items(file_name: &str, what: &str) -> Result<Vec<Item>> {
+		let pem = read_pem(file_name, what)?;
+		rustls_pemfile::read_all(&mut pem.as_slice())
+			.with_context(|| format!("{} file {} is not a valid PEM file", what, file_name))
+	}
+
+	fn load_certified_key(cert_file_name: &str, key_file_name: &str) -> Result<CertifiedKey> {
+		let cert_chain: Vec<Certificate> = read_pem_items(cert_file_name, "certificate")?.into_iter()
+			.filter_map(|item| match item {
+				Item::X509Certificate(der) => Some(Certificate(der)),
+				_ => None,
+			})
+			.collect();
+		if cert_chain.is_empty() {
+			return Err(anyhow!("no certificates found in {}", cert_file_name));
+		}
+
+		let key = read_pem_items(key_file_name, "private key")?.into_iter()
+			.find_map(|item| match item {
+				Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+				_ => None,
+			})
+			.ok_or_else(|| anyhow!("no private key found in {}, expected a PKCS#8, RSA or EC PEM key", key_file_name))?;
+		let key = sign::any_supported_type(&key)
+			.with_context(|| format!("unsupported private key in {}", key_file_name))?;
+		Ok(CertifiedKey::new(cert_chain, key))
+	}
+
+	/// With `client_ca_file_name` every client must present a certificate issued by one of
+	/// the CAs in that file.
+	pub fn rustls_config(resolver: Arc<CertResolver>, client_ca_file_name: Option<&str>) -> Result<ServerConfig> {
+		let builder = ServerConfig::builder().with_safe_defaults();
+		let Some(ca_file_name) = client_ca_file_name else {
+			return Ok(builder.with_no_client_auth().with_cert_resolver(resolver));
+		};
+
+		let mut roots = RootCertStore::empty();
+		for item in read_pem_items(ca_file_name, "client CA")? {
+			if let Item::X509Certificate(der) = item {
+				roots.add(&Certificate(der)).with_context(|| format!("invalid CA certificate in {}", ca_file_name))?;
+			}
+		}
+		if roots.is_empty() {
+			return Err(anyhow!("no CA certificates found in {}", ca_file_name));
+		}
+		Ok(builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots)).with_cert_resolver(resolver))
+	}
+
+	/// Reloads the certificate on SIGHUP or when the cert/key files change on disk.
+	pub async fn watch_certs(resolver: Arc<CertResolver>) {
+		#[cfg(unix)]
+		let mut hangup = match signal(SignalKind::hangup()) {
+			Ok(hangup) => Some(hangup),
+			Err(err) => {
+				warn!("can't listen for SIGHUP, certificates reload on file change only: {}", err);
+				None
+			}
+		};
+		let mut ticker = actix_web::rt::time::interval(CERT_WATCH_INTERVAL);
+		let mut modified = resolver.modified();
+
+		loop {
+			#[cfg(unix)]
+			let hangup_received = async {
+				match hangup.as_mut() {
+					Some(hangup) => hangup.recv().await,
+					None => std::future::pending().await,
+				}
+			};
+			#[cfg(not(unix))]
+			let hangup_received = std::future::pending::<Option<()>>();
+
+			tokio::select! {
+				_ = hangup_received => {
+					info!("SIGHUP received, reloading TLS certificate");
+					resolver.reload();
+					modified = resolver.modified();
+				}
+				_ = ticker.tick() => {
+					let current = resolver.modified();
+					if current.is_some() && current != modified {
+						info!("TLS certificate files changed, reloading");
+						resolver.reload();
+						modified = current;
+					}
+				}
+			}
+		}
+	}
+
+	/// TLS stream carrying how long its handshake took into the http service.
+	pub struct TimedTlsStream {
+		io: TlsStream<TcpStream>,
+		handshake: Duration,
+	}
+
+	impl TimedTlsStream {
+		fn connect_info(&self) -> ConnectInfo {
+			let (tcp, session) = self.io.get_ref();
+			ConnectInfo{
+				peer: tcp.peer_addr().ok(),
+				handshake: Some(Handshake{version: protocol_name(session.protocol_version()), duration: self.handshake}),
+				client_cert: session.peer_certificates()
+					.and_then(|chain| chain.first())
+					.and_then(|cert| ClientCert::from_der(&cert.0)),
+				#[cfg(unix)]
+				fd: Some(tcp.as_raw_fd()),
+			}
+		}
+	}
+
+	impl AsyncRead for TimedTlsStream {
+		fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+			Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+		}
+	}
+
+	impl AsyncWrite for TimedTlsStream {
+		fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+			Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+		}
+
+		fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+			Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+		}
+
+		fn is_write_vectored(&self) -> bool {
+			self.io.is_write_vectored()
+		}
+
+		fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+			Pin::new(&mut self.get_mut().io).poll_flush(cx)
+		}
+
+		fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+			Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+		}
+	}
+
+	/// Builds the https server, timing every handshake from socket accept to completion.
+	/// `on_connect` sees every connection once its handshake is done.
+	pub fn rustls_server<F, I, S, B, C>(
+		factory: F,
+		listener: net::TcpListener,
+		mut config: ServerConfig,
+		on_connect: C,
+		workers: usize,
+		connections: usize,
+	) -> io::Result<Server>
+	where
+		C: Fn(ConnectInfo, &mut Extensions) + Send + Clone + 'static,
+		F: Fn() -> I + Send + Clone + 'static,
+		I: IntoServiceFactory<S, Request>,
+		S: ServiceFactory<Request, Config = AppConfig> + 'static,
+		S::Error: Into<actix_web::Error> + 'static,
+		S::InitError: fmt::Debug,
+		S::Response: Into<Response<B>> + 'static,
+		<S::Service as Service<Request>>::Future: 'static,
+		S::Service: 'static,
+		B: MessageBody + 'static,
+	{
+		config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+		let acceptor = Acceptor::new(config);
+
+		let addr = listener.local_addr()?;
+
+		// signals are handled by the caller, see shutdown::stop_on_signal
+		let mut builder = Server::build().disable_signals();
+		if workers > 0 {
+			builder = builder.workers(workers);
+		}
+		if connections > 0 {
+			builder = builder.max_concurrent_connections(connections);
+		}
+
+		Ok(builder.listen("bench-server-https", listener, move || {
+			let on_connect = on_connect.clone();
+			let app = factory()
+				.into_factory()
+				.map_err(|err| err.into().error_response());
+			let http = HttpService::build()
+				.client_disconnect_timeout(Duration::from_secs(1))
+				.local_addr(addr)
+				.on_connect_ext(move |io: &TimedTlsStream, ext: &mut Extensions| on_connect(io.connect_info(), ext))
+				.finish(map_config(app, move |_| super::app_config(addr)));
+
+			apply_fn_factory::<_, _, _, _, TcpStream, TcpStream, _, _>(acceptor.clone(), |io, acceptor: &AcceptorService| {
+				let start = Instant::now();
+				let accept = Service::<TcpStream>::call(acceptor, io);
+				async move { Ok((accept.await?, start.elapsed())) }
+			})
+			.map_init_err(|_| unreachable!("TLS acceptor service factory does not error on init"))
+			.map_err(TlsError::into_service_error)
+			.and_then(|(io, handshake): (TlsStream<TcpStream>, Duration)| async move {
+				let (tcp, session) = io.get_ref();
+				let proto = match session.alpn_protocol() {
+					Some(b"h2") => Protocol::Http2,
+					_ => Protocol::Http1,
+				};
+				let peer_addr = tcp.peer_addr().ok();
+				Ok((TimedTlsStream{io, handshake}, proto, peer_addr))
+			})
+			.and_then(http.map_err(TlsError::Service))
+		})?.run())
+	}
+
+	/// Same version names openssl reports, so both backends share metric labels.
+	fn protocol_name(version: Option<ProtocolVersion>) -> &'static str {
+		match version {
+			Some(ProtocolVersion::TLSv1_2) => "TLSv1.2",
+			Some(ProtocolVersion::TLSv1_3) => "TLSv1.3",
+			_ => "unknown",
+		}
+	}
+}
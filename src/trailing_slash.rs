@@ -0,0 +1,78 @@
+use actix_web::{
+	body::{BoxBody, MessageBody},
+	dev::{ServiceRequest, ServiceResponse},
+	http::header,
+	middleware::Next,
+	HttpResponse,
+};
+use anyhow::anyhow;
+use std::fmt;
+use std::str::FromStr;
+
+/// How paths with a trailing slash are routed, `--trailing-slash`.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+	/// `/get/` is another path than `/get`, and a 404
+	#[default]
+	Strict,
+	/// `/get/` is routed like `/get`, by actix's `NormalizePath`
+	Merge,
+	/// `/get/` gets a 301 to `/get`
+	Redirect,
+}
+
+impl FromStr for TrailingSlash {
+	type Err = anyhow::Error;
+
+	fn from_str(mode: &str) -> anyhow::Result<Self> {
+		match mode {
+			"strict" => Ok(TrailingSlash::Strict),
+			"merge" => Ok(TrailingSlash::Merge),
+			"redirect" => Ok(TrailingSlash::Redirect),
+			_ => Err(anyhow!("unknown trailing slash mode {}, expected strict, merge or redirect", mode)),
+		}
+	}
+}
+
+impl fmt::Display for TrailingSlash {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			TrailingSlash::Strict => "strict",
+			TrailingSlash::Merge => "merge",
+			TrailingSlash::Redirect => "redirect",
+		})
+	}
+}
+
+/// The path `NormalizePath::trim()` would route: repeated slashes merged, trailing ones
+/// dropped, `/` staying `/`.
+pub fn canonical(path: &str) -> String {
+	let mut canonical = String::with_capacity(path.len());
+	for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+		canonical.push('/');
+		canonical.push_str(segment);
+	}
+	if canonical.is_empty() {
+		canonical.push('/');
+	}
+	canonical
+}
+
+/// `--trailing-slash redirect`: answers non canonical paths with a 301 to the canonical one,
+/// keeping the query.
+pub async fn redirect(
+	req: ServiceRequest,
+	next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+	let path = req.path();
+	let canonical = canonical(path);
+	if canonical == path {
+		return Ok(next.call(req).await?.map_into_boxed_body());
+	}
+	let location = match req.query_string() {
+		"" => canonical,
+		query => format!("{}?{}", canonical, query),
+	};
+	let response = HttpResponse::MovedPermanently().insert_header((header::LOCATION, location)).finish();
+	Ok(req.into_response(response))
+}
@@ -0,0 +1,155 @@
+use actix_multipart::Multipart;
+use actix_web::{http::{header, StatusCode}, web, HttpMessage, HttpRequest, HttpResponse};
+use base64::Engine;
+use futures_util::StreamExt;
+use serde_json::{json, Map, Value};
+use std::time::Instant;
+
+use crate::error::BenchError;
+use crate::headers::declared_trailers;
+use crate::MaxBodySize;
+
+/// `POST /chunked-upload`: reads the body as a stream and reports the size of every chunk
+/// as actix handed it over, with the total and how long the upload took. Chunks follow the
+/// client's writes as far as the network and actix's read buffer let them through, they
+/// aren't the `Transfer-Encoding: chunked` framing itself. Capped by `--max-body-size`.
+/// Trailers are reported like `/echo-trailers` does, announced names only.
+pub async fn chunked_upload(req: HttpRequest, mut body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	let limit = req.app_data::<MaxBodySize>().map_or(usize::MAX, |limit| limit.0);
+	let start = Instant::now();
+	let mut chunks = Vec::new();
+	let mut total = 0;
+	while let Some(chunk) = body.next().await {
+		let chunk = chunk?;
+		total += chunk.len();
+		if total > limit {
+			return Err(BenchError::new(StatusCode::PAYLOAD_TOO_LARGE, format!("body exceeds {} bytes", limit)).into());
+		}
+		chunks.push(chunk.len());
+	}
+
+	let body = json!({
+		"chunks": chunks,
+		"total_bytes": total,
+		"duration_ms": start.elapsed().as_millis() as u64,
+		"declared_trailers": declared_trailers(&req),
+		"trailers": {},
+	});
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string()))
+}
+
+/// Adds `value` under `name`, a name sent more than once gets the list of its values.
+fn insert_repeated(map: &mut Map<String, Value>, name: String, value: Value) {
+	match map.get_mut(&name) {
+		Some(Value::Array(values)) => values.push(value),
+		Some(first) => *first = Value::Array(vec![first.take(), value]),
+		None => {
+			map.insert(name, value);
+		}
+	}
+}
+
+/// `POST /post-multipart-echo`: reads every part of a `multipart/form-data` body and answers
+/// `{"fields": {name: text}, "files": {name: {"filename", "content_type", "data"}}}`, file
+/// contents base64 encoded. Parts without a filename are fields if they are UTF-8 and files
+/// otherwise. Capped by `--max-body-size` over all parts.
+pub async fn multipart_echo(req: HttpRequest, body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	let form_data = req.mime_type().ok().flatten().is_some_and(|mime| mime.essence_str() == mime::MULTIPART_FORM_DATA.essence_str());
+	if !form_data {
+		return Err(BenchError::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "expected a multipart/form-data body").into());
+	}
+	let mut multipart = Multipart::new(req.headers(), body);
+	let limit = req.app_data::<MaxBodySize>().map_or(usize::MAX, |limit| limit.0);
+	let mut total = 0;
+	let (mut fields, mut files) = (Map::new(), Map::new());
+	while let Some(field) = multipart.next().await {
+		let mut field = field?;
+		let name = field.name().unwrap_or_default().to_string();
+		let filename = field.content_disposition().and_then(|disposition| disposition.get_filename()).map(str::to_string);
+		let content_type = field.content_type().map(|mime| mime.to_string());
+		let mut data = Vec::new();
+		while let Some(chunk) = field.next().await {
+			let chunk = chunk?;
+			total += chunk.len();
+			if total > limit {
+				return Err(BenchError::new(StatusCode::PAYLOAD_TOO_LARGE, format!("body exceeds {} bytes", limit)).into());
+			}
+			data.extend_from_slice(&chunk);
+		}
+
+		if filename.is_none() {
+			match String::from_utf8(data) {
+				Ok(text) => {
+					insert_repeated(&mut fields, name, text.into());
+					continue;
+				}
+				Err(err) => data = err.into_bytes(),
+			}
+		}
+		let file = json!({
+			"filename": filename,
+			"content_type": content_type,
+			"size": data.len(),
+			"data": base64::engine::general_purpose::STANDARD.encode(data),
+		});
+		insert_repeated(&mut files, name, file);
+	}
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"fields": fields, "files": files}).to_string()))
+}
+
+/// When the bytes of a request body came in, counted as it is read and then dropped.
+struct BodyTiming {
+	start: Instant,
+	first: Option<Instant>,
+	last: Option<Instant>,
+	bytes: usize,
+	chunks: usize,
+}
+
+impl BodyTiming {
+	fn new() -> Self {
+		BodyTiming{start: Instant::now(), first: None, last: None, bytes: 0, chunks: 0}
+	}
+
+	fn observe(&mut self, chunk: &[u8]) {
+		let now = Instant::now();
+		self.first.get_or_insert(now);
+		self.last = Some(now);
+		self.bytes += chunk.len();
+		self.chunks += 1;
+	}
+
+	fn body(&self, req: &HttpRequest) -> serde_json::Value {
+		let micros = |from: Instant, to: Option<Instant>| to.map(|to| to.duration_since(from).as_micros() as u64);
+		let transfer = self.first.zip(self.last).map(|(first, last)| last.duration_since(first));
+		json!({
+			"bytes": self.bytes,
+			"chunks": self.chunks,
+			"first_byte_us": micros(self.start, self.first),
+			"last_byte_us": micros(self.start, self.last),
+			"transfer_us": transfer.map(|transfer| transfer.as_micros() as u64),
+			"bytes_per_sec": transfer.filter(|transfer| !transfer.is_zero()).map(|transfer| (self.bytes as f64 / transfer.as_secs_f64()) as u64),
+			"declared_trailers": declared_trailers(req),
+			"trailers": {},
+		})
+	}
+}
+
+/// `POST /reflect-timing`: reads the body without keeping it and answers when its bytes
+/// arrived, in microseconds since the handler started, i.e. since the request head was
+/// parsed. `transfer_us` is first to last byte, the upload time as the server saw it,
+/// separate from anything the server does with the body. Trailers as in `/echo-trailers`.
+pub async fn reflect_timing(req: HttpRequest, mut body: web::Payload) -> Result<HttpResponse, actix_web::Error> {
+	let mut timing = BodyTiming::new();
+	while let Some(chunk) = body.next().await {
+		timing.observe(&chunk?);
+	}
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(timing.body(&req).to_string()))
+}
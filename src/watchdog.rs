@@ -0,0 +1,49 @@
+use actix_web::{rt, web};
+use log::warn;
+use std::cell::Cell;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::stats::Stats;
+
+pub const DEFAULT_INTERVAL_MS: u64 = 100;
+pub const DEFAULT_THRESHOLD_MS: u64 = 50;
+
+thread_local! {
+	/// Whether this worker already runs a watchdog, further app instances on it share it.
+	static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// `--watchdog-interval` and `--watchdog-threshold`, a zero interval turns it off.
+#[derive(Clone, Copy)]
+pub struct Watchdog {
+	pub interval: Duration,
+	pub threshold: Duration,
+}
+
+impl Watchdog {
+	/// Starts watching the worker the app factory runs on, once per worker.
+	pub fn start(self, stats: &web::Data<Stats>) -> Self {
+		if !self.interval.is_zero() && !RUNNING.with(|running| running.replace(true)) {
+			rt::spawn(watch(self, stats.clone()));
+		}
+		self
+	}
+}
+
+/// Sleeps `interval` over and over, timing how much later than asked for it wakes up. A
+/// worker's requests share its thread, so anything blocking it delays the wake up as much
+/// as the requests waiting behind it.
+async fn watch(watchdog: Watchdog, stats: web::Data<Stats>) {
+	let worker = std::thread::current().name().unwrap_or("worker").to_string();
+	loop {
+		let scheduled = Instant::now() + watchdog.interval;
+		rt::time::sleep(watchdog.interval).await;
+		let latency = Instant::now().saturating_duration_since(scheduled);
+		stats.watchdog.max_latency_us.fetch_max(latency.as_micros() as u64, Ordering::Relaxed);
+		if latency > watchdog.threshold {
+			stats.watchdog.stalls.fetch_add(1, Ordering::Relaxed);
+			warn!("{} stalled: scheduled {:?} late, something blocks its event loop", worker, latency);
+		}
+	}
+}
@@ -0,0 +1,66 @@
+use actix_web::{http::{header, StatusCode}, web, HttpResponse};
+use dashmap::DashMap;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::error::BenchError;
+
+/// Items kept per key, so an unattended run can't exhaust memory.
+const MAX_ITEMS: usize = 1_000_000;
+
+/// Lists behind `/benchmark/concurrent-writes`, every writer to a key queues on its mutex.
+#[derive(Default)]
+pub struct ConcurrentWrites {
+	lists: DashMap<String, Arc<Mutex<Vec<web::Bytes>>>>,
+}
+
+impl ConcurrentWrites {
+	fn list(&self, key: &str) -> Arc<Mutex<Vec<web::Bytes>>> {
+		self.lists.entry(key.to_string()).or_default().clone()
+	}
+
+	pub fn reset(&self) {
+		self.lists.clear();
+	}
+}
+
+#[derive(Deserialize)]
+pub struct WritesQuery {
+	key: String,
+}
+
+/// `POST /benchmark/concurrent-writes?key=<k>`: appends the body to the key's list and
+/// returns the new item count.
+pub async fn append(query: web::Query<WritesQuery>, body: web::Bytes, writes: web::Data<ConcurrentWrites>) -> Result<HttpResponse, BenchError> {
+	let list = writes.list(&query.key);
+	let mut items = list.lock().await;
+	if items.len() >= MAX_ITEMS {
+		return Err(BenchError::new(StatusCode::INSUFFICIENT_STORAGE, format!("key {} already holds {} items", query.key, MAX_ITEMS)));
+	}
+	items.push(body);
+	let count = items.len();
+	drop(items);
+
+	Ok(HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(json!({"key": query.key, "count": count}).to_string()))
+}
+
+/// `GET /benchmark/concurrent-writes?key=<k>`: the key's items in write order, as text.
+pub async fn list(query: web::Query<WritesQuery>, writes: web::Data<ConcurrentWrites>) -> HttpResponse {
+	// reading doesn't create the key
+	let list = writes.lists.get(&query.key).map(|list| list.clone()).unwrap_or_default();
+	let items = list.lock().await;
+	let body = json!({
+		"key": query.key,
+		"count": items.len(),
+		"items": items.iter().map(|item| String::from_utf8_lossy(item)).collect::<Vec<_>>(),
+	});
+	drop(items);
+
+	HttpResponse::Ok()
+	.insert_header(header::ContentType(mime::APPLICATION_JSON))
+	.body(body.to_string())
+}
@@ -0,0 +1,86 @@
+use actix_web::{rt, web, HttpRequest, HttpResponse};
+use actix_ws::{Message, MessageStream, Session};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::error::BenchError;
+use crate::stats::{Stats, Tracked};
+
+const DEFAULT_RATE: u32 = 100;
+const MAX_RATE: u32 = 100_000;
+const DEFAULT_SIZE: usize = 64;
+const MAX_SIZE: usize = 1024 * 1024;
+
+/// Timers below a millisecond are not reliable, faster rates send several messages per tick.
+const MIN_TICK: Duration = Duration::from_millis(1);
+
+#[derive(Deserialize)]
+pub struct WsBenchQuery {
+	rate: Option<u32>,
+	size: Option<usize>,
+}
+
+/// `/ws-bench?rate=<msgs/s>&size=<bytes>`: pushes binary messages to the client at a fixed
+/// rate until it disconnects.
+pub async fn ws_bench(
+	req: HttpRequest,
+	body: web::Payload,
+	query: web::Query<WsBenchQuery>,
+	stats: web::Data<Stats>,
+) -> Result<HttpResponse, actix_web::Error> {
+	let rate = query.rate.unwrap_or(DEFAULT_RATE);
+	if rate == 0 || rate > MAX_RATE {
+		return Err(BenchError::bad_request(format!("rate must be between 1 and {}", MAX_RATE)).into());
+	}
+	let size = query.size.unwrap_or(DEFAULT_SIZE);
+	if size > MAX_SIZE {
+		return Err(BenchError::bad_request(format!("size must be at most {} bytes", MAX_SIZE)).into());
+	}
+
+	let (response, session, messages) = actix_ws::handle(&req, body)?;
+	rt::spawn(push(session, messages, rate, size, stats));
+	Ok(response)
+}
+
+async fn push(mut session: Session, mut messages: MessageStream, rate: u32, size: usize, stats: web::Data<Stats>) {
+	let _active = Tracked::new(stats.clone(), |stats| &stats.ws_bench.active);
+	let payload = web::Bytes::from(vec![b'x'; size]);
+
+	let tick = Duration::from_secs_f64(1.0 / rate as f64).max(MIN_TICK);
+	// messages owed, a tick's share of the rate is fractional above 1000 msg/s
+	let per_tick = rate as f64 * tick.as_secs_f64();
+	let mut credit = 0.0;
+	let mut ticker = rt::time::interval(tick);
+
+	loop {
+		tokio::select! {
+			_ = ticker.tick() => {
+				credit += per_tick;
+				let due = credit.floor();
+				credit -= due;
+				for _ in 0..due as u32 {
+					if session.binary(payload.clone()).await.is_err() {
+						return;
+					}
+					stats.ws_bench.messages_sent.fetch_add(1, Ordering::Relaxed);
+					stats.ws_bench.bytes_sent.fetch_add(size as u64, Ordering::Relaxed);
+				}
+			}
+			message = messages.next() => match message {
+				Some(Ok(Message::Ping(bytes))) => {
+					if session.pong(&bytes).await.is_err() {
+						return;
+					}
+				}
+				Some(Ok(Message::Close(reason))) => {
+					let _ = session.close(reason).await;
+					return;
+				}
+				Some(Ok(_)) => {}
+				Some(Err(_)) | None => return,
+			},
+		}
+	}
+}